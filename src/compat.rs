@@ -0,0 +1,113 @@
+//! Drop-in replacements for the API of other arena crates, to ease migrating to this one.
+//!
+//! This module is only available if the `bumpalo-compat` feature is enabled.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::DroplessArena;
+
+/// A bump allocator with the most commonly used methods of `bumpalo::Bump`, backed by a
+/// [`DroplessArena`].
+///
+/// ```rust
+/// use stable_arena::compat::Bump;
+///
+/// let bump = Bump::new();
+/// let x = bump.alloc(42);
+/// assert_eq!(*x, 42);
+/// assert_eq!(bump.alloc_slice_copy(&[1, 2, 3]), &[1, 2, 3]);
+/// assert_eq!(bump.alloc_str("hello"), "hello");
+/// ```
+///
+/// It differs from `bumpalo::Bump` in these ways:
+///
+/// - `reset` keeps the longest chunk for reuse, like `DroplessArena::clear`. Bumpalo keeps the
+///   most recently allocated chunk instead, which is usually also the longest one.
+/// - `allocated_bytes` counts the capacity of the chunks, without bumpalo's chunk footer.
+/// - There is no allocation limit, no `try_alloc*` methods and no `Allocator` implementation
+///   (the `nightly` feature implements `Allocator` for `&DroplessArena`, though).
+///
+/// As with bumpalo, values that need to be dropped can be allocated, but they are never dropped.
+#[derive(Debug, Default)]
+pub struct Bump {
+    arena: DroplessArena,
+}
+
+impl Bump {
+    /// Creates a new, empty bump allocator. It doesn't allocate until it is first used.
+    pub fn new() -> Bump {
+        Bump::default()
+    }
+
+    /// Creates a new bump allocator whose first chunk can hold at least `bytes` bytes.
+    pub fn with_capacity(bytes: usize) -> Bump {
+        Bump {
+            arena: DroplessArena::with_capacity(bytes),
+        }
+    }
+
+    /// Allocates `val` in the bump allocator, returning a mutable reference to it.
+    ///
+    /// Unlike `DroplessArena::alloc`, this accepts types that need to be dropped and zero-sized
+    /// types. The value is never dropped.
+    #[inline]
+    #[track_caller]
+    pub fn alloc<T>(&self, val: T) -> &mut T {
+        self.alloc_with(|| val)
+    }
+
+    /// Allocates the value returned by `f` in the bump allocator, returning a mutable reference
+    /// to it. The space is reserved before `f` is called, so the value may be constructed in
+    /// place.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_with<T, F: FnOnce() -> T>(&self, f: F) -> &mut T {
+        let layout = Layout::new::<T>();
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            self.arena.alloc_raw(layout).cast::<T>()
+        };
+        // SAFETY: `ptr` is aligned and valid for writes of a `T`, and the memory is not used by
+        // any other allocation.
+        unsafe {
+            ptr.write(f());
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copies `src` into the bump allocator, returning a mutable reference to the copy.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        if src.is_empty() || size_of::<T>() == 0 {
+            // SAFETY: A zero-sized slice doesn't need any memory. Its elements are copies of the
+            // ones in `src`, which takes no bytes.
+            return unsafe {
+                NonNull::slice_from_raw_parts(NonNull::dangling(), src.len()).as_mut()
+            };
+        }
+        self.arena.alloc_slice(src)
+    }
+
+    /// Copies `src` into the bump allocator, returning a mutable reference to the copy.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str(&self, src: &str) -> &mut str {
+        let bytes = self.alloc_slice_copy(src.as_bytes());
+        // SAFETY: The bytes are a copy of valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked_mut(bytes) }
+    }
+
+    /// Frees everything that was allocated, keeping the longest chunk for reuse. No destructors
+    /// are run.
+    pub fn reset(&mut self) {
+        self.arena.clear();
+    }
+
+    /// Returns the total capacity of all chunks, in bytes.
+    pub fn allocated_bytes(&self) -> usize {
+        self.arena.memory_reserved()
+    }
+}
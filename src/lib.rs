@@ -5,7 +5,7 @@
 //! of individual objects while the arena itself is still alive. The benefit
 //! of an arena is very fast allocation; just a pointer bump.
 //!
-//! This crate implements two kinds of arena.
+//! This crate implements five kinds of arena.
 //!
 //! # For types that need to be dropped: `TypedArena`
 //! `TypedArena` is used like this:
@@ -65,34 +65,107 @@
 //! b.other.set(Some(a));
 //! ```
 //!
+//! # For objects of many types that need to be dropped: `DropArena`
+//! A `DropArena` can hold objects of any type, and records a destructor for each object that
+//! needs to be dropped. The destructors run in reverse allocation order when the arena is
+//! dropped.
+//!
+//! ```rust
+//! use stable_arena::DropArena;
+//!
+//! let arena = DropArena::default();
+//! let x = arena.alloc(Box::new(42));
+//! let y = arena.alloc(String::from("hello"));
+//! assert_eq!(**x, 42);
+//! assert_eq!(y, "hello");
+//! ```
+//!
+//! # For referring to objects by index: `IndexedArena`
+//! An `IndexedArena` wraps a `TypedArena` and returns an `Idx` for each allocated object, which
+//! can be turned back into a reference with `get`.
+//!
+//! ```rust
+//! use stable_arena::IndexedArena;
+//!
+//! let arena = IndexedArena::default();
+//! let a = arena.push("a".to_string());
+//! let b = arena.push("b".to_string());
+//! assert_eq!(arena.get(b), "b");
+//! assert_eq!(arena.get(a), "a");
+//! ```
+//!
+//! # For sharing between threads: `SyncDroplessArena`
+//! A `SyncDroplessArena` is like a `DroplessArena`, but it is `Sync`, so several threads can
+//! allocate in it at once.
+//!
+//! # `no_std`
+//!
+//! This crate is `#![no_std]`: it only needs `core` and `alloc`, and therefore works on any
+//! target with a global allocator. The optional `std` feature only changes how `ArenaPool` and
+//! `DroplessArenaPool` are locked.
+//!
 //! # Features
 //!
 //! - The `from-iter` feature enables the `alloc_from_iter` method on both arenas. This feature is
 //!   enabled by default.
+//! - The `nightly` feature implements the unstable `Allocator` trait for `&DroplessArena`, so that
+//!   an arena can back collections like `Vec::new_in`, and adds `DroplessArena::alloc_as`, which
+//!   returns an unsized view of the object like `&mut dyn Trait`. This feature requires a
+//!   nightly compiler.
+//! - The `debug-checks` feature makes `clear` and dropping a `TypedArena` overwrite the memory of
+//!   the destroyed objects with `0xDD` bytes, so that dangling references (e.g. derived from raw
+//!   pointers) are easier to notice.
+//! - The `std` feature makes `ArenaPool` and `DroplessArenaPool` use a `std::sync::Mutex`, so
+//!   that threads waiting for the pool sleep. Without it, they spin, which is fine as the lock
+//!   is only held to push or pop an arena, but wastes CPU time if a thread holding the lock is
+//!   descheduled.
+//! - The `zeroize` feature overwrites the memory of objects with zeros when it is freed or
+//!   reused, i.e. on `clear`, `reset`, `rewind` and when an arena is dropped, so that sensitive
+//!   data (e.g. keys) doesn't linger in memory. The writes are volatile, so they aren't
+//!   optimized away. This takes precedence over `debug-checks`. Objects that are moved out of a
+//!   `TypedArena` with `drain` or `clear_and_collect` are not scrubbed.
+//! - The `bumpalo-compat` feature enables the `compat` module with a `Bump` type that mirrors
+//!   the most common methods of `bumpalo::Bump`, for migrating from that crate.
 
 #![no_std]
+#![cfg_attr(feature = "nightly", feature(allocator_api, unsize))]
 #![allow(clippy::mut_from_ref)] // Arena allocators are one place where this pattern is fine.
 
+// Why `alloc(&self) -> &mut T` is sound: every allocation hands out a region of memory that no
+// other allocation overlaps, so the returned `&mut T` is the only reference to it. This relies on
+// two things. First, the bump pointers live in `Cell`s, so the arenas must not be `Sync`:
+// otherwise two threads could bump the same pointer and get the same region. (`SyncDroplessArena`
+// uses atomics instead.) Second, anything that looks at existing objects, like `clear` or `iter`,
+// takes `&mut self`, so no `&mut T` from `alloc` can still be alive. The `!Sync` property is
+// checked in the tests.
+
 extern crate alloc;
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 use core::alloc::Layout;
+use core::any::type_name;
+use core::borrow::Borrow;
 use core::cell::{Cell, RefCell};
 #[cfg(feature = "from-iter")]
 use core::convert::Infallible;
+use core::ffi::CStr;
 use core::hint::assert_unchecked;
 use core::marker::PhantomData;
 use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::pin::Pin;
 use core::ptr::{self, NonNull};
-use core::{cmp, slice};
+use core::str::Utf8Error;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{cmp, fmt, slice};
 
 #[cfg(feature = "from-iter")]
 use smallvec::SmallVec;
 
-#[cfg(feature = "from-iter")]
 /// This calls the passed function while ensuring it won't be inlined into the caller.
 #[inline(never)]
 #[cold]
@@ -100,8 +173,45 @@ fn outline<F: FnOnce() -> R, R>(f: F) -> R {
     f()
 }
 
+/// Passes on the first `max_elems` values of `iter`, followed by an error if there are more.
+///
+/// The `take` also bounds the size hint, so a lying or huge hint can't make the collection
+/// reserve more than `max_elems + 1` elements.
+#[cfg(feature = "from-iter")]
+fn cap_iter<T>(
+    iter: impl IntoIterator<Item = T>,
+    max_elems: usize,
+) -> impl Iterator<Item = Result<T, CapExceeded>> {
+    iter.into_iter()
+        .take(max_elems.saturating_add(1))
+        .enumerate()
+        .map(move |(i, value)| {
+            if i < max_elems {
+                Ok(value)
+            } else {
+                Err(CapExceeded { max_elems })
+            }
+        })
+}
+
+/// Collects the values of `iter` up to the first error.
+///
+/// Unlike collecting into a `Result`, this reserves room for the iterator's lower size bound up
+/// front, so the buffer isn't grown repeatedly for iterators with a good lower bound.
+#[cfg(feature = "from-iter")]
+fn try_collect<T, E>(iter: impl IntoIterator<Item = Result<T, E>>) -> Result<SmallVec<[T; 8]>, E> {
+    let iter = iter.into_iter();
+    let mut vec = SmallVec::with_capacity(iter.size_hint().0);
+    for item in iter {
+        vec.push(item?);
+    }
+    Ok(vec)
+}
+
 struct ArenaChunk<T = u8> {
     /// The raw storage for the arena chunk.
+    ///
+    /// Being a boxed slice of `T`, this is suitably aligned for `T`, however large its alignment.
     storage: NonNull<[MaybeUninit<T>]>,
     /// The number of valid entries in the chunk.
     entries: usize,
@@ -113,16 +223,75 @@ impl<T> Drop for ArenaChunk<T> {
     }
 }
 
+/// Initialized elements that still need to be dropped. If dropping one of them panics, the
+/// others are still dropped when the guard is dropped during unwinding, like `drop_in_place`
+/// does for slices.
+struct DropRest<'a, T>(&'a mut [MaybeUninit<T>]);
+
+impl<T> DropRest<'_, T> {
+    /// Drops the elements back to front.
+    fn drop_elements(&mut self) {
+        while let Some((last, rest)) = mem::take(&mut self.0).split_last_mut() {
+            self.0 = rest;
+            // SAFETY: The elements are initialized, and `last` is no longer part of `self`, so
+            // it isn't dropped again.
+            unsafe { last.assume_init_drop() };
+        }
+    }
+}
+
+impl<T> Drop for DropRest<'_, T> {
+    fn drop(&mut self) {
+        self.drop_elements();
+    }
+}
+
+/// Deallocates all of `chunks` but the longest one. Of several equally long chunks, the last one
+/// is kept.
+fn keep_longest<T>(chunks: &mut Vec<ArenaChunk<T>>) {
+    let Some(longest) = chunks
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, chunk)| chunk.storage.len())
+        .map(|(i, _)| i)
+    else {
+        return;
+    };
+    let chunk = chunks.swap_remove(longest);
+    chunks.clear();
+    chunks.push(chunk);
+}
+
 impl<T> ArenaChunk<T> {
     #[inline]
+    #[track_caller]
     unsafe fn new(capacity: usize) -> ArenaChunk<T> {
+        if Layout::array::<T>(capacity).is_err() {
+            array_too_large::<T>(capacity);
+        }
         ArenaChunk {
             storage: NonNull::from(Box::leak(Box::new_uninit_slice(capacity))),
             entries: 0,
         }
     }
 
-    /// Destroys this arena chunk.
+    /// Like `new`, but returns `None` instead of aborting if the allocation fails.
+    ///
+    /// `T` must not be zero-sized and `capacity` must not be zero.
+    #[inline]
+    unsafe fn try_new(capacity: usize) -> Option<ArenaChunk<T>> {
+        let layout = Layout::array::<T>(capacity).ok()?;
+        debug_assert!(layout.size() != 0);
+        // SAFETY: The layout has a non-zero size. The memory is allocated with the same layout
+        // that `Box<[MaybeUninit<T>]>` uses, so `Drop` can free it through `Box::from_raw`.
+        let ptr = NonNull::new(unsafe { alloc::alloc::alloc(layout) })?;
+        Some(ArenaChunk {
+            storage: NonNull::slice_from_raw_parts(ptr.cast(), capacity),
+            entries: 0,
+        })
+    }
+
+    /// Destroys this arena chunk, dropping the elements in reverse order.
     ///
     /// # Safety
     ///
@@ -136,20 +305,23 @@ impl<T> ArenaChunk<T> {
             // been initialized.
             unsafe {
                 let slice = self.storage.as_mut();
-                slice[..len].assume_init_drop();
+                let mut rest = DropRest(&mut slice[..len]);
+                rest.drop_elements();
             }
         }
+        // SAFETY: The caller guarantees that at least `len` elements were allocated.
+        unsafe { poison(self.start().cast(), len * size_of::<T>()) };
     }
 
     // Returns a pointer to the first allocated object.
     #[inline]
-    fn start(&mut self) -> *mut T {
+    fn start(&self) -> *mut T {
         self.storage.as_ptr() as *mut T
     }
 
     // Returns a pointer to the end of the allocated space.
     #[inline]
-    fn end(&mut self) -> *mut T {
+    fn end(&self) -> *mut T {
         unsafe {
             if size_of::<T>() == 0 {
                 // A pointer as large as possible for zero-sized elements.
@@ -169,7 +341,150 @@ impl<T> ArenaChunk<T> {
 const PAGE: usize = 4096;
 const HUGE_PAGE: usize = 2 * 1024 * 1024;
 
+/// Memory statistics of an arena, as returned by `TypedArena::stats` and `DroplessArena::stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The number of chunks the arena has allocated.
+    pub chunk_count: usize,
+    /// The total capacity of all chunks, in bytes.
+    pub bytes_reserved: usize,
+    /// The number of bytes taken up by allocated objects, including alignment padding.
+    pub bytes_used: usize,
+    /// The number of reserved bytes that are not used, i.e. `bytes_reserved - bytes_used`.
+    pub bytes_wasted: usize,
+}
+
+/// Controls how the chunks of a `TypedArena` or `DroplessArena` grow.
+///
+/// The first chunk is `initial_chunk_bytes` large; every following chunk is `growth_factor` times
+/// as large as its predecessor, but at most `max_chunk_bytes`. A chunk is always made large enough
+/// for the allocation that triggered it, even if that exceeds `max_chunk_bytes`.
+///
+/// The default configuration starts with 4 KiB chunks and doubles them up to 2 MiB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaConfig {
+    /// The factor by which each chunk is larger than the previous one. Values below `1.0` are
+    /// treated as `1.0`.
+    pub growth_factor: f64,
+    /// The size of the first chunk, in bytes.
+    pub initial_chunk_bytes: usize,
+    /// The size beyond which chunks stop growing, in bytes.
+    pub max_chunk_bytes: usize,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> ArenaConfig {
+        ArenaConfig {
+            growth_factor: 2.0,
+            initial_chunk_bytes: PAGE,
+            max_chunk_bytes: HUGE_PAGE,
+        }
+    }
+}
+
+impl ArenaConfig {
+    /// Returns the size in bytes of the chunk following one of `prev_bytes` bytes, or of the first
+    /// chunk if `prev_bytes` is `None`.
+    fn next_chunk_bytes(&self, prev_bytes: Option<usize>) -> usize {
+        match prev_bytes {
+            None => self.initial_chunk_bytes,
+            Some(prev) => {
+                // Float-to-int casts saturate, so this can't overflow.
+                let grown = (prev as f64 * self.growth_factor) as usize;
+                cmp::max(prev, grown).min(cmp::max(prev, self.max_chunk_bytes))
+            }
+        }
+    }
+}
+
+/// The error returned by `DroplessArena::try_alloc_c_str` when the string contains a NUL byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NulError {
+    position: usize,
+}
+
+impl NulError {
+    /// Returns the position of the first NUL byte in the string.
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for NulError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "nul byte found in provided data at position: {}",
+            self.position
+        )
+    }
+}
+
+impl core::error::Error for NulError {}
+
+/// A position in a `DroplessArena` that it can be rolled back to, returned by
+/// `DroplessArena::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The number of chunks at the time of the checkpoint.
+    chunk_count: usize,
+    /// The address of the end of the free space in the last chunk.
+    end: usize,
+}
+
+/// Which chunk `TypedArena::clear_with` and `DroplessArena::clear_with` keep for reuse by
+/// subsequent allocations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClearPolicy {
+    /// Keep the longest chunk. This is what `clear` does.
+    #[default]
+    KeepLongest,
+    /// Keep the first chunk, which is the smallest one unless `DroplessArena::absorb` has moved
+    /// in chunks before it. This caps the memory that is held on to after a spike in usage.
+    KeepFirst,
+    /// Deallocate all chunks.
+    KeepNone,
+}
+
+/// The error returned by `DroplessArena::try_reserve` when memory can't be allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl core::error::Error for AllocError {}
+
+/// The error returned by `alloc_from_iter_capped` when the iterator yields more elements than
+/// allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapExceeded {
+    max_elems: usize,
+}
+
+impl CapExceeded {
+    /// Returns the maximum number of elements that was exceeded.
+    pub fn max_elems(&self) -> usize {
+        self.max_elems
+    }
+}
+
+impl fmt::Display for CapExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "iterator yielded more than {} elements", self.max_elems)
+    }
+}
+
+impl core::error::Error for CapExceeded {}
+
 /// An arena that can hold objects of only one type.
+///
+/// When the arena is dropped or cleared, its objects are dropped in reverse allocation order:
+/// the object allocated last is dropped first. (The elements of a slice count as allocated in
+/// order, so they are dropped back to front.)
 pub struct TypedArena<T> {
     /// A pointer to the next object to be allocated.
     ptr: Cell<*mut T>,
@@ -181,6 +496,22 @@ pub struct TypedArena<T> {
     /// A vector of arena chunks.
     chunks: RefCell<Vec<ArenaChunk<T>>>,
 
+    /// Empty chunks that `reset` kept around, to be reused before allocating new ones. The
+    /// chunk to be reused first comes last.
+    spare_chunks: RefCell<Vec<ArenaChunk<T>>>,
+
+    /// For the first few chunks (never the last one), the number of objects in the chunk and
+    /// all chunks before it. This is filled in lazily by `get`, and truncated when chunks are
+    /// removed.
+    chunk_ends: Vec<usize>,
+
+    /// The number of slots handed out by `alloc_uninit` that haven't been passed to
+    /// `assume_init_entry` yet.
+    uninit: Cell<usize>,
+
+    /// Determines the sizes of new chunks.
+    config: ArenaConfig,
+
     /// Marker indicating that dropping the arena causes its owned
     /// instances of `T` to be dropped.
     _own: PhantomData<T>,
@@ -189,20 +520,71 @@ pub struct TypedArena<T> {
 impl<T> Default for TypedArena<T> {
     /// Creates a new `TypedArena`.
     fn default() -> TypedArena<T> {
+        TypedArena::with_config(ArenaConfig::default())
+    }
+}
+
+impl<T> FromIterator<T> for TypedArena<T> {
+    /// Creates a new `TypedArena` holding the elements of the iterator, in order.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> TypedArena<T> {
+        let iter = iter.into_iter();
+        let arena = TypedArena::with_capacity(iter.size_hint().0);
+        for object in iter {
+            arena.alloc(object);
+        }
+        arena
+    }
+}
+
+impl<T> TypedArena<T> {
+    /// Creates a new `TypedArena` whose chunks grow according to `config`.
+    pub fn with_config(config: ArenaConfig) -> TypedArena<T> {
         TypedArena {
             // We set both `ptr` and `end` to 0 so that the first call to
             // alloc() will trigger a grow().
             ptr: Cell::new(ptr::null_mut()),
             end: Cell::new(ptr::null_mut()),
             chunks: Default::default(),
+            spare_chunks: Default::default(),
+            chunk_ends: Vec::new(),
+            uninit: Cell::new(0),
+            config,
             _own: PhantomData,
         }
     }
-}
 
-impl<T> TypedArena<T> {
+    /// Creates a new `TypedArena` whose first chunk can hold at least `n` objects.
+    ///
+    /// The first `n` allocations will then not need to allocate a new chunk. For zero-sized
+    /// types this is equivalent to `TypedArena::default()`.
+    #[track_caller]
+    pub fn with_capacity(n: usize) -> TypedArena<T> {
+        let arena = TypedArena::default();
+        if n != 0 && size_of::<T>() != 0 {
+            arena.grow(n);
+        }
+        arena
+    }
+
+    /// Ensures that at least `additional` more objects can be allocated without allocating a new
+    /// chunk. A new chunk is allocated if the current one is too small.
+    ///
+    /// This does nothing for zero-sized types.
+    #[track_caller]
+    pub fn reserve(&self, additional: usize) {
+        if additional != 0 && size_of::<T>() != 0 && !self.can_allocate(additional) {
+            self.grow(additional);
+        }
+    }
+
     /// Allocates an object in the `TypedArena`, returning a mutable reference to it.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types, which don't need an arena: no count of them is kept, so there is
+    ///    nothing that could overflow
     #[inline]
+    #[track_caller]
     pub fn alloc(&self, object: T) -> &mut T {
         assert!(size_of::<T>() != 0);
 
@@ -220,14 +602,155 @@ impl<T> TypedArena<T> {
         }
     }
 
-    #[cfg(feature = "from-iter")]
+    /// Like [`alloc`](Self::alloc), but returns a shared reference, which can be copied freely.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_shared(&self, object: T) -> &T {
+        self.alloc(object)
+    }
+
+    /// Tries to allocate an object in the `TypedArena`, returning a mutable reference to it.
+    ///
+    /// If a new chunk is needed and allocating it fails, the object is handed back as `Err`
+    /// instead of aborting the process.
+    #[inline]
+    #[track_caller]
+    pub fn try_alloc(&self, object: T) -> Result<&mut T, T> {
+        assert!(size_of::<T>() != 0);
+
+        if self.ptr == self.end && self.try_grow(1).is_none() {
+            return Err(object);
+        }
+
+        unsafe {
+            let ptr = self.ptr.get();
+            // Advance the pointer.
+            self.ptr.set(self.ptr.get().add(1));
+            // Write into uninitialized memory.
+            ptr::write(ptr, object);
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Allocates an object in the `TypedArena` that is produced by `f`, returning a mutable
+    /// reference to it.
+    ///
+    /// The slot is reserved before `f` is called, which gives the optimizer the chance to
+    /// construct the object in place instead of building it on the stack and then copying it into
+    /// the arena. This is not guaranteed, but it often happens, which makes this useful for large
+    /// objects.
+    ///
+    /// If `f` panics, the slot is given back to the arena. If `f` has allocated in this arena
+    /// before panicking, this is not possible and the process is aborted.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_with<F: FnOnce() -> T>(&self, f: F) -> &mut T {
+        assert!(size_of::<T>() != 0);
+
+        // SAFETY: `guard` ensures that the slot is either initialized, or given back to the arena
+        // (aborting if that's not possible) so that it's never dropped uninitialized.
+        let slot = unsafe { self.alloc_raw_slice(1) };
+        let guard = FillGuard {
+            arena: self,
+            start: slot,
+            len: 1,
+            written: 0,
+            chunk_count: self.chunks.borrow().len(),
+        };
+        // SAFETY: The slot is valid for writes.
+        unsafe { ptr::write(slot, f()) };
+        mem::forget(guard);
+
+        // SAFETY: The slot has been initialized.
+        unsafe { &mut *slot }
+    }
+
+    /// Allocates a default-initialized object in the `TypedArena`, returning a mutable
+    /// reference to it.
+    ///
+    /// Like [`alloc_with`](Self::alloc_with), this reserves the slot before calling
+    /// `T::default()`, so the object can be constructed in place.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_default(&self) -> &mut T
+    where
+        T: Default,
+    {
+        self.alloc_with(T::default)
+    }
+
+    /// Allocates an uninitialized slot in the `TypedArena`, for objects that have to be
+    /// constructed in their final location, e.g. because they point into themselves.
+    ///
+    /// Once the slot is initialized, it must be passed to
+    /// [`assume_init_entry`](Self::assume_init_entry). Until all slots have been passed to it,
+    /// the arena can't tell which of its objects are initialized, so:
+    ///
+    /// - methods that read the objects, like `iter`, panic, and
+    /// - clearing or dropping the arena leaks all objects instead of dropping them.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_uninit(&self) -> &mut MaybeUninit<T> {
+        assert!(size_of::<T>() != 0);
+
+        // SAFETY: `self.uninit` makes sure that the slot is not treated as initialized.
+        let slot = unsafe { self.alloc_raw_slice(1) };
+        self.uninit.set(self.uninit.get() + 1);
+        // SAFETY: The slot is valid for writes, and `MaybeUninit` may be uninitialized.
+        unsafe { &mut *(slot as *mut MaybeUninit<T>) }
+    }
+
+    /// Registers a slot from [`alloc_uninit`](Self::alloc_uninit) as initialized, so that the
+    /// arena drops it like its other objects, and returns a reference to the object.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must have been returned by `alloc_uninit` on this arena, must be initialized, and
+    /// must not have been passed to this method before.
+    #[inline]
+    pub unsafe fn assume_init_entry<'a>(&'a self, slot: &'a mut MaybeUninit<T>) -> &'a mut T {
+        debug_assert!(self.uninit.get() != 0);
+        self.uninit.set(self.uninit.get() - 1);
+        // SAFETY: The caller guarantees that the slot is initialized.
+        unsafe { slot.assume_init_mut() }
+    }
+
+    /// Panics if some slots from `alloc_uninit` may not be initialized.
+    fn assert_initialized(&self) {
+        assert!(
+            self.uninit.get() == 0,
+            "arena holds slots from `alloc_uninit` that were not passed to `assume_init_entry`"
+        );
+    }
+
+    /// If some slots from `alloc_uninit` may not be initialized, makes the arena forget all of
+    /// its objects, so that they are leaked instead of dropped.
+    fn forget_if_uninit(&mut self) {
+        if mem::take(self.uninit.get_mut()) != 0 {
+            self.forget_objects();
+        }
+    }
+
+    /// Makes the arena forget all of its objects, without dropping them.
+    fn forget_objects(&mut self) {
+        let chunks = self.chunks.get_mut();
+        for chunk in chunks.iter_mut() {
+            chunk.entries = 0;
+        }
+        if let Some(last_chunk) = chunks.last() {
+            self.ptr.set(last_chunk.start());
+        }
+    }
+
     #[inline]
     fn can_allocate(&self, additional: usize) -> bool {
         // FIXME: this should *likely* use `offset_from`, but more
         // investigation is needed (including running tests in miri).
         let available_bytes = self.end.get().addr() - self.ptr.get().addr();
-        let additional_bytes = additional.checked_mul(size_of::<T>()).unwrap();
-        available_bytes >= additional_bytes
+        // If the size overflows, the objects can't fit in any chunk, and `grow` reports that.
+        additional
+            .checked_mul(size_of::<T>())
+            .is_some_and(|additional_bytes| available_bytes >= additional_bytes)
     }
 
     /// Allocates storage for `len >= 1` values in this arena, and returns a
@@ -244,8 +767,8 @@ impl<T> TypedArena<T> {
     ///
     /// FIXME(Zalathar): This is *very* fragile; perhaps we need a different
     /// approach to arena-allocating slices of droppable values.
-    #[cfg(feature = "from-iter")]
     #[inline]
+    #[track_caller]
     unsafe fn alloc_raw_slice(&self, len: usize) -> *mut T {
         assert!(size_of::<T>() != 0);
         assert!(len != 0);
@@ -263,14 +786,118 @@ impl<T> TypedArena<T> {
         start_ptr
     }
 
+    /// Allocates a slice of objects that are copied into the `TypedArena`, returning a mutable
+    /// reference to it.
+    ///
+    /// Returns an empty slice if `slice` is empty.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_slice(&self, slice: &[T]) -> &mut [T]
+    where
+        T: Copy,
+    {
+        assert!(size_of::<T>() != 0);
+        if slice.is_empty() {
+            return &mut [];
+        }
+
+        // SAFETY: Copying can't panic, so the storage is fully initialized right away.
+        unsafe {
+            let start_ptr = self.alloc_raw_slice(slice.len());
+            start_ptr.copy_from_nonoverlapping(slice.as_ptr(), slice.len());
+            slice::from_raw_parts_mut(start_ptr, slice.len())
+        }
+    }
+
+    /// Allocates an array in the `TypedArena`, returning a mutable reference to it. Each element
+    /// is dropped along with the arena, like any other object.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_array<const N: usize>(&self, array: [T; N]) -> &mut [T; N] {
+        self.alloc_from_iter_exact(array).try_into().unwrap()
+    }
+
+    /// Allocates a slice of `count` objects in the `TypedArena`, initializing the element at index
+    /// `i` with `f(i)`, and returns a mutable reference to it. Returns an empty slice if `count`
+    /// is zero.
+    ///
+    /// The elements are constructed in place. If `f` panics, the elements constructed so far are
+    /// dropped, as with `alloc_from_iter_exact`.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice(&self, count: usize, f: impl FnMut(usize) -> T) -> &mut [T] {
+        self.alloc_from_iter_exact((0..count).map(f))
+    }
+
+    /// Returns a [`SliceBuilder`] that collects objects one at a time and then moves them into a
+    /// contiguous slice in the `TypedArena`. This doesn't allocate.
+    pub fn slice_builder(&self) -> SliceBuilder<'_, T> {
+        SliceBuilder {
+            arena: self,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Allocates the elements of this exact-size iterator into a contiguous slice in the
+    /// `TypedArena`.
+    ///
+    /// Unlike `alloc_from_iter`, this writes the elements directly into the arena instead of
+    /// collecting them into a temporary buffer first. If the iterator panics, the elements written
+    /// so far are dropped and their storage is given back to the arena.
+    ///
+    /// Panics if the iterator yields fewer elements than its `len()`; extra elements are ignored.
+    /// If this happens while the iterator is itself allocating in this arena, the storage cannot
+    /// be given back and the process is aborted.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_iter_exact<I>(&self, iter: I) -> &mut [T]
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        assert!(size_of::<T>() != 0);
+
+        let mut iter = iter.into_iter();
+        let len = iter.len();
+        if len == 0 {
+            return &mut [];
+        }
+
+        // SAFETY: `guard` ensures that the storage is either fully initialized, or given back to
+        // the arena (aborting if that's not possible) so that it's never dropped uninitialized.
+        let start = unsafe { self.alloc_raw_slice(len) };
+        let mut guard = FillGuard {
+            arena: self,
+            start,
+            len,
+            written: 0,
+            chunk_count: self.chunks.borrow().len(),
+        };
+        while guard.written < len {
+            match iter.next() {
+                // SAFETY: `written < len`, so this is within the storage we allocated.
+                Some(value) => unsafe { start.add(guard.written).write(value) },
+                None => panic!("iterator yielded fewer elements than its `len()`"),
+            }
+            guard.written += 1;
+        }
+        mem::forget(guard);
+
+        // SAFETY: All `len` elements have been initialized.
+        unsafe { slice::from_raw_parts_mut(start, len) }
+    }
+
     #[cfg(feature = "from-iter")]
-    /// Allocates the elements of this iterator into a contiguous slice in the `TypedArena`.
+    /// Allocates the elements of this iterator into a contiguous slice in the `TypedArena`. The
+    /// elements are dropped along with the arena, like any other object.
     ///
     /// Note: for reasons of reentrancy and panic safety we collect into a `SmallVec<[_; 8]>` before
-    /// storing the elements in the arena.
+    /// storing the elements in the arena. If the iterator panics, the elements collected so far
+    /// are dropped and the arena is left unchanged.
     ///
     /// This function is only available if the `from-iter` feature is enabled.
     #[inline]
+    #[track_caller]
     pub fn alloc_from_iter<I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
         // Note: `Infallible` stands in for the never type `!` used upstream (see the README).
         match self.try_alloc_from_iter(iter.into_iter().map(Ok::<T, Infallible>)) {
@@ -287,6 +914,7 @@ impl<T> TypedArena<T> {
     ///
     /// This function is only available if the `from-iter` feature is enabled.
     #[inline]
+    #[track_caller]
     pub fn try_alloc_from_iter<E>(
         &self,
         iter: impl IntoIterator<Item = Result<T, E>>,
@@ -305,8 +933,7 @@ impl<T> TypedArena<T> {
         // doesn't need to be hyper-optimized.
         assert!(size_of::<T>() != 0);
 
-        let vec: Result<SmallVec<[T; 8]>, E> = iter.into_iter().collect();
-        let mut vec = vec?;
+        let mut vec = try_collect(iter)?;
         if vec.is_empty() {
             return Ok(&mut []);
         }
@@ -326,45 +953,425 @@ impl<T> TypedArena<T> {
         })
     }
 
-    /// Grows the arena.
-    #[inline(never)]
-    #[cold]
-    fn grow(&self, additional: usize) {
+    #[cfg(feature = "from-iter")]
+    /// Like [`alloc_from_iter`](Self::alloc_from_iter), but fails if the iterator yields more
+    /// than `max_elems` elements, e.g. because its length is controlled by untrusted input. The
+    /// elements collected until then are dropped.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_iter_capped<I: IntoIterator<Item = T>>(
+        &self,
+        iter: I,
+        max_elems: usize,
+    ) -> Result<&mut [T], CapExceeded> {
+        self.try_alloc_from_iter(cap_iter(iter, max_elems))
+    }
+
+    #[cfg(feature = "from-iter")]
+    /// Clones the elements of `slice` into a contiguous slice in the `TypedArena`.
+    ///
+    /// Unlike `DroplessArena::alloc_slice`, this works for types that need to be dropped: the
+    /// clones are owned by this arena and are dropped together with it.
+    ///
+    /// The elements are cloned via `alloc_from_iter`, so a panicking `clone` drops the clones
+    /// made so far and leaves the arena untouched.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_clone(&self, slice: &[T]) -> &mut [T]
+    where
+        T: Clone,
+    {
+        self.alloc_from_iter(slice.iter().cloned())
+    }
+
+    /// Clears the arena, running drop glue on every object allocated so far, in reverse
+    /// allocation order.
+    ///
+    /// Deallocates all but the longest chunk, which is kept and reused by subsequent
+    /// allocations.
+    ///
+    /// Any references handed out before the call are invalidated; this is enforced by the
+    /// `&mut self` receiver.
+    pub fn clear(&mut self) {
+        self.clear_with(ClearPolicy::KeepLongest);
+    }
+
+    /// Clears the arena like `clear`, but `policy` decides which chunk, if any, is kept.
+    pub fn clear_with(&mut self, policy: ClearPolicy) {
+        self.forget_if_uninit();
         unsafe {
-            // We need the element size to convert chunk sizes (ranging from
-            // PAGE to HUGE_PAGE bytes) to element counts.
-            let elem_size = cmp::max(1, size_of::<T>());
-            let mut chunks = self.chunks.borrow_mut();
-            let mut new_cap;
-            if let Some(last_chunk) = chunks.last_mut() {
-                // If a type is `!needs_drop`, we don't need to keep track of how many elements
-                // the chunk stores - the field will be ignored anyway.
-                if mem::needs_drop::<T>() {
-                    // FIXME: this should *likely* use `offset_from`, but more
-                    // investigation is needed (including running tests in miri).
-                    let used_bytes = self.ptr.get().addr() - last_chunk.start().addr();
-                    last_chunk.entries = used_bytes / size_of::<T>();
+            // Clear the last chunk, which is partially filled.
+            let mut chunks_borrow = self.chunks.borrow_mut();
+            if let Some(last_chunk) = chunks_borrow.last_mut() {
+                self.clear_last_chunk(last_chunk);
+                let len = chunks_borrow.len();
+                // Zero-sized types can't be allocated, so there are no chunks for them.
+                for chunk in chunks_borrow[..len - 1].iter_mut().rev() {
+                    chunk.destroy(chunk.entries);
                 }
-
-                // If the previous chunk's len is less than HUGE_PAGE
-                // bytes, then this chunk will be least double the previous
-                // chunk's size.
-                new_cap = last_chunk.storage.len().min(HUGE_PAGE / elem_size / 2);
-                new_cap *= 2;
-            } else {
-                new_cap = PAGE / elem_size;
             }
-            // Also ensure that this chunk can fit `additional`.
-            new_cap = cmp::max(additional, new_cap);
+            // The spare chunks that `reset` kept are empty, and can be kept instead. The one to be
+            // reused first comes last.
+            let mut spare_chunks = mem::take(self.spare_chunks.get_mut());
+            match policy {
+                ClearPolicy::KeepLongest => {
+                    chunks_borrow.append(&mut spare_chunks);
+                    keep_longest(&mut chunks_borrow);
+                }
+                ClearPolicy::KeepFirst => {
+                    if chunks_borrow.is_empty() {
+                        chunks_borrow.extend(spare_chunks.pop());
+                    }
+                    chunks_borrow.truncate(1);
+                }
+                ClearPolicy::KeepNone => chunks_borrow.clear(),
+            }
+            let (ptr, end) = match chunks_borrow.first() {
+                Some(chunk) => (chunk.start(), chunk.end()),
+                None => (ptr::null_mut(), ptr::null_mut()),
+            };
+            self.ptr.set(ptr);
+            self.end.set(end);
+        }
+        self.chunk_ends.clear();
+    }
 
-            let chunk = chunks.push_mut(ArenaChunk::<T>::new(new_cap));
-            self.ptr.set(chunk.start());
-            self.end.set(chunk.end());
+    /// Clears the arena like `clear`, but keeps all chunks for reuse by subsequent allocations,
+    /// instead of only the longest one.
+    ///
+    /// This suits loops that need about the same amount of memory in every round: after the
+    /// first round, the arena doesn't allocate anymore. `clear` instead needs to allocate again
+    /// whenever a round outgrows the longest chunk.
+    pub fn reset(&mut self) {
+        self.forget_if_uninit();
+        let chunks = self.chunks.get_mut();
+        if let Some(last_chunk) = chunks.last_mut() {
+            // The last chunk's `entries` is not kept up to date.
+            last_chunk.entries =
+                (self.ptr.get().addr() - last_chunk.start().addr()) / size_of::<T>();
+        }
+        let spare_chunks = self.spare_chunks.get_mut();
+        // Drop the objects last to first. As the spare chunks are taken from the end, this also
+        // makes them to be reused in the order they were filled.
+        for mut chunk in chunks.drain(..).rev() {
+            // SAFETY: The chunk holds `entries` objects.
+            unsafe { chunk.destroy(chunk.entries) };
+            chunk.entries = 0;
+            spare_chunks.push(chunk);
         }
+        // The next allocation takes a spare chunk.
+        self.ptr.set(ptr::null_mut());
+        self.end.set(ptr::null_mut());
+        self.chunk_ends.clear();
     }
 
-    // Drops the contents of the last chunk. The last chunk is partially empty, unlike all other
-    // chunks.
+    /// Returns an iterator that moves the objects out of the arena, in allocation order.
+    ///
+    /// The arena is left empty, but keeps all of its chunks for reuse, like after `reset`. The
+    /// objects that the iterator doesn't yield are dropped together with it.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        let slices: Vec<NonNull<[T]>> = self.filled_slices().map(NonNull::from).collect();
+        // The objects now belong to the iterator. If it is leaked, so are they.
+        self.forget_objects();
+        self.reset();
+        Drain {
+            slices,
+            chunk: 0,
+            index: 0,
+            _arena: PhantomData,
+        }
+    }
+
+    /// Clears the arena like `clear`, but moves the objects for which `f` returns `true` out of the
+    /// arena and returns them, in allocation order. The other objects are dropped.
+    pub fn clear_and_collect<F: FnMut(&T) -> bool>(&mut self, f: F) -> Vec<T> {
+        let mut objects: Vec<T> = Vec::with_capacity(self.len());
+        for slice in self.filled_slices() {
+            // SAFETY: The objects are moved into `objects`, and the arena is reset below, before
+            // anything can panic, so it doesn't drop them again.
+            unsafe {
+                objects
+                    .as_mut_ptr()
+                    .add(objects.len())
+                    .copy_from_nonoverlapping(slice.as_ptr(), slice.len());
+                objects.set_len(objects.len() + slice.len());
+            }
+        }
+        let chunks = self.chunks.get_mut();
+        if let Some(len) = chunks.len().checked_sub(1) {
+            // Keep the last chunk, which is the longest one, like `clear` does.
+            chunks.drain(..len);
+            self.ptr.set(chunks[0].start());
+        }
+        self.spare_chunks.get_mut().clear();
+        self.chunk_ends.clear();
+
+        objects.retain(f);
+        objects
+    }
+
+    /// Turns the arena into a read-only [`FrozenArena`], which can be shared between threads.
+    pub fn freeze(mut self) -> FrozenArena<T> {
+        self.assert_initialized();
+        let ptr = self.ptr.get();
+        // `self` is left without chunks, so dropping it does nothing.
+        let mut chunks = mem::take(self.chunks.get_mut());
+        if let Some(last_chunk) = chunks.last_mut() {
+            // Chunks only exist for non-zero-sized `T`, so the division is fine.
+            last_chunk.entries = (ptr.addr() - last_chunk.start().addr()) / size_of::<T>();
+        }
+        let ends = chunks
+            .iter()
+            .scan(0, |len, chunk| {
+                *len += chunk.entries;
+                Some(*len)
+            })
+            .collect();
+        FrozenArena {
+            chunks,
+            ends,
+            _own: PhantomData,
+        }
+    }
+
+    /// Deallocates the chunks at the end of the arena that hold no objects, e.g. the chunk that
+    /// `clear` keeps around. If the arena is empty, this frees all of its memory.
+    pub fn shrink_to_fit(&mut self) {
+        self.spare_chunks.get_mut().clear();
+        let chunks = self.chunks.get_mut();
+        // Only the last chunk is tracked by `self.ptr`; the others know their `entries`.
+        while chunks
+            .last()
+            .is_some_and(|last_chunk| self.ptr.get() == last_chunk.start())
+        {
+            chunks.pop();
+            let (ptr, end) = match chunks.last() {
+                // SAFETY: The chunk holds `entries` objects.
+                Some(chunk) => (unsafe { chunk.start().add(chunk.entries) }, chunk.end()),
+                None => (ptr::null_mut(), ptr::null_mut()),
+            };
+            self.ptr.set(ptr);
+            self.end.set(end);
+        }
+        self.chunk_ends.truncate(chunks.len().saturating_sub(1));
+    }
+
+    /// Returns memory statistics for this arena. This takes time linear in the number of chunks.
+    pub fn stats(&self) -> ArenaStats {
+        let chunks = self.chunks.borrow();
+        let spare_chunks = self.spare_chunks.borrow();
+        let mut stats = ArenaStats {
+            chunk_count: chunks.len() + spare_chunks.len(),
+            bytes_reserved: self.memory_reserved(),
+            ..ArenaStats::default()
+        };
+        for (i, chunk) in chunks.iter().enumerate() {
+            stats.bytes_used += if i == chunks.len() - 1 {
+                // The last chunk's `entries` is not kept up to date.
+                self.ptr.get().addr() - chunk.start().addr()
+            } else {
+                chunk.entries * size_of::<T>()
+            };
+        }
+        stats.bytes_wasted = stats.bytes_reserved - stats.bytes_used;
+        stats
+    }
+
+    /// Returns the number of objects in the arena. This takes time linear in the number of chunks.
+    pub fn len(&self) -> usize {
+        if size_of::<T>() == 0 {
+            // Zero-sized types can't be allocated.
+            return 0;
+        }
+        self.memory_used() / size_of::<T>()
+    }
+
+    /// Returns whether the arena holds no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of objects that the chunks of the arena can hold in total, including
+    /// the objects that are already allocated. Returns `usize::MAX` for zero-sized types.
+    ///
+    /// This counts the space that was left over at the end of earlier chunks, when a slice
+    /// didn't fit into them, although that space is not reused.
+    pub fn capacity(&self) -> usize {
+        if size_of::<T>() == 0 {
+            return usize::MAX;
+        }
+        self.memory_reserved() / size_of::<T>()
+    }
+
+    /// Returns the number of bytes taken up by allocated objects. This is the same as
+    /// `self.stats().bytes_used`.
+    pub fn memory_used(&self) -> usize {
+        self.stats().bytes_used
+    }
+
+    /// Returns the total capacity of all chunks, in bytes. This is the same as
+    /// `self.stats().bytes_reserved`.
+    pub fn memory_reserved(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        let spare_chunks = self.spare_chunks.borrow();
+        chunks
+            .iter()
+            .chain(spare_chunks.iter())
+            .map(|chunk| chunk.storage.len())
+            .sum::<usize>()
+            * size_of::<T>()
+    }
+
+    /// Returns the object at position `index` in allocation order, or `None` if out of bounds.
+    /// This takes time logarithmic in the number of chunks.
+    ///
+    /// Like [`iter`](Self::iter), this takes `&mut self`, so that it can't alias the references
+    /// that `alloc` hands out.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        self.assert_initialized();
+        let chunks = self.chunks.get_mut();
+        // Record the lengths of the full chunks that were added since the last call.
+        let full_chunks = chunks.len().saturating_sub(1);
+        for chunk in &chunks[self.chunk_ends.len()..full_chunks] {
+            let end = self.chunk_ends.last().copied().unwrap_or(0) + chunk.entries;
+            self.chunk_ends.push(end);
+        }
+        let chunk = self.chunk_ends.partition_point(|&end| end <= index);
+        let start = match chunk.checked_sub(1) {
+            Some(prev) => self.chunk_ends[prev],
+            None => 0,
+        };
+        let is_last = chunk == full_chunks;
+        let chunk = chunks.get(chunk)?;
+        let len = if is_last {
+            // The last chunk's `entries` is not kept up to date.
+            (self.ptr.get().addr() - chunk.start().addr()) / size_of::<T>()
+        } else {
+            chunk.entries
+        };
+        let offset = index - start;
+        // SAFETY: The first `len` elements of the chunk have been initialized, and the
+        // `&mut self` borrow ensures that no mutable references to them exist.
+        (offset < len).then(|| unsafe { &*chunk.start().add(offset) })
+    }
+
+    /// Returns an iterator over all objects in the arena, in allocation order.
+    ///
+    /// This takes `&mut self` because `alloc` hands out `&mut T` references from `&self`; a
+    /// shared borrow would let those alias the references yielded here.
+    pub fn iter(&mut self) -> impl Iterator<Item = &T> {
+        self.filled_slices().flat_map(|slice| slice.iter())
+    }
+
+    /// Returns an iterator that allows modifying all objects in the arena, in allocation order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.filled_slices().flat_map(|slice| slice.iter_mut())
+    }
+
+    /// Returns the objects in the arena as one contiguous slice per chunk, in allocation order.
+    ///
+    /// Concatenating the slices gives the same sequence as [`iter`](Self::iter). Like `iter`,
+    /// this takes `&mut self` so that the slices cannot alias references returned by `alloc`.
+    pub fn as_slices(&mut self) -> impl Iterator<Item = &[T]> {
+        self.filled_slices().map(|slice| &*slice)
+    }
+
+    /// Returns the filled part of each chunk, in allocation order.
+    fn filled_slices(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.assert_initialized();
+        let ptr = self.ptr.get();
+        let chunks = self.chunks.get_mut();
+        let last = chunks.len().wrapping_sub(1);
+        chunks.iter_mut().enumerate().map(move |(i, chunk)| {
+            // The last chunk is partially filled and its `entries` is not kept up to date, so
+            // we compute its length from `ptr`. Chunks only exist for non-zero-sized `T`, so
+            // the division is fine.
+            let len = if i == last {
+                (ptr.addr() - chunk.start().addr()) / size_of::<T>()
+            } else {
+                chunk.entries
+            };
+            // SAFETY: The first `len` elements of the chunk have been initialized, and the
+            // `&mut self` borrow ensures that no other references to them exist.
+            unsafe { slice::from_raw_parts_mut(chunk.start(), len) }
+        })
+    }
+
+    /// Grows the arena.
+    #[inline(never)]
+    #[cold]
+    #[track_caller]
+    fn grow(&self, additional: usize) {
+        unsafe {
+            let mut chunks = self.chunks.borrow_mut();
+            let new_cap = self.new_chunk_capacity(&mut chunks, additional);
+            let chunk = match self.take_spare_chunk(additional) {
+                Some(chunk) => chunks.push_mut(chunk),
+                None => chunks.push_mut(ArenaChunk::<T>::new(new_cap)),
+            };
+            self.ptr.set(chunk.start());
+            self.end.set(chunk.end());
+        }
+    }
+
+    /// Grows the arena, returning `None` instead of aborting if the allocation fails.
+    #[inline(never)]
+    #[cold]
+    fn try_grow(&self, additional: usize) -> Option<()> {
+        unsafe {
+            let mut chunks = self.chunks.borrow_mut();
+            let new_cap = self.new_chunk_capacity(&mut chunks, additional);
+            let chunk = match self.take_spare_chunk(additional) {
+                Some(chunk) => chunks.push_mut(chunk),
+                None => chunks.push_mut(ArenaChunk::<T>::try_new(new_cap)?),
+            };
+            self.ptr.set(chunk.start());
+            self.end.set(chunk.end());
+            Some(())
+        }
+    }
+
+    /// Takes the next spare chunk that can fit `additional` objects. The spare chunks before it
+    /// are too small and are deallocated, so that the chunks keep growing in size.
+    fn take_spare_chunk(&self, additional: usize) -> Option<ArenaChunk<T>> {
+        let mut spare_chunks = self.spare_chunks.borrow_mut();
+        // The spare chunks get longer towards the front, so the first one that fits fits best.
+        while spare_chunks.last()?.storage.len() < additional {
+            spare_chunks.pop();
+        }
+        spare_chunks.pop()
+    }
+
+    /// Computes the capacity of the next chunk, which must fit at least `additional` objects.
+    ///
+    /// This also records the number of entries in the current last chunk.
+    fn new_chunk_capacity(&self, chunks: &mut [ArenaChunk<T>], additional: usize) -> usize {
+        // We need the element size to convert chunk sizes in bytes to element counts.
+        let elem_size = cmp::max(1, size_of::<T>());
+        let new_cap;
+        if let Some(last_chunk) = chunks.last_mut() {
+            // Record how many elements the chunk stores. This is needed both for running
+            // drop glue and for iterating over the arena's contents.
+            // FIXME: this should *likely* use `offset_from`, but more
+            // investigation is needed (including running tests in miri).
+            let used_bytes = self.ptr.get().addr() - last_chunk.start().addr();
+            last_chunk.entries = used_bytes / size_of::<T>();
+
+            let prev_bytes = last_chunk.storage.len() * elem_size;
+            new_cap = self.config.next_chunk_bytes(Some(prev_bytes)) / elem_size;
+        } else {
+            new_cap = self.config.next_chunk_bytes(None) / elem_size;
+        }
+        // Also ensure that this chunk can fit `additional`.
+        cmp::max(additional, new_cap)
+    }
+
+    // Drops the contents of the last chunk. The last chunk is partially empty, unlike all other
+    // chunks.
     fn clear_last_chunk(&self, last_chunk: &mut ArenaChunk<T>) {
         // Determine how much was filled.
         let start = last_chunk.start().addr();
@@ -385,8 +1392,43 @@ impl<T> TypedArena<T> {
     }
 }
 
+/// Cleans up after `TypedArena::alloc_from_iter_exact` if it is interrupted by a panic.
+struct FillGuard<'a, T> {
+    arena: &'a TypedArena<T>,
+    /// The start of the storage that is being filled.
+    start: *mut T,
+    /// The number of elements the storage can hold.
+    len: usize,
+    /// The number of elements that have been initialized so far.
+    written: usize,
+    /// The number of chunks in the arena right after the storage was allocated.
+    chunk_count: usize,
+}
+
+impl<T> Drop for FillGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The first `written` elements have been initialized, and nothing else refers to
+        // them yet.
+        unsafe { ptr::slice_from_raw_parts_mut(self.start, self.written).drop_in_place() };
+
+        // The storage can only be given back if nothing was allocated after it in the meantime.
+        let end = self.start.wrapping_add(self.len);
+        if self.arena.ptr.get() == end && self.arena.chunks.borrow().len() == self.chunk_count {
+            self.arena.ptr.set(self.start);
+        } else {
+            // The uninitialized storage is now followed by other objects, and the arena would
+            // eventually drop it. The guard only runs during unwinding, so panicking here aborts
+            // the process.
+            panic!(
+                "arena was allocated into while a partially initialized allocation was unwinding"
+            );
+        }
+    }
+}
+
 impl<T> Drop for TypedArena<T> {
     fn drop(&mut self) {
+        self.forget_if_uninit();
         unsafe {
             // Determine how much was filled.
             let mut chunks_borrow = self.chunks.borrow_mut();
@@ -394,7 +1436,7 @@ impl<T> Drop for TypedArena<T> {
                 // Drop the contents of the last chunk.
                 self.clear_last_chunk(&mut last_chunk);
                 // The last chunk will be dropped. Destroy all other chunks.
-                for chunk in chunks_borrow.iter_mut() {
+                for chunk in chunks_borrow.iter_mut().rev() {
                     chunk.destroy(chunk.entries);
                 }
             }
@@ -405,6 +1447,239 @@ impl<T> Drop for TypedArena<T> {
 
 unsafe impl<T: Send> Send for TypedArena<T> {}
 
+/// The objects of a [`TypedArena`] after [`freeze`](TypedArena::freeze) has been called.
+///
+/// Nothing can be allocated in a frozen arena, so unlike `TypedArena`, it hands out shared
+/// references from `&self` and is `Sync` if `T` is. The objects are dropped together with the
+/// frozen arena, in the same order as in `TypedArena`.
+pub struct FrozenArena<T> {
+    /// The chunks of the arena, all with up-to-date `entries`.
+    chunks: Vec<ArenaChunk<T>>,
+    /// For each chunk, the number of objects in it and all chunks before it.
+    ends: Vec<usize>,
+    _own: PhantomData<T>,
+}
+
+impl<T> FrozenArena<T> {
+    /// Returns the number of objects in the arena.
+    pub fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    /// Returns whether the arena holds no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the object at position `index` in allocation order, or `None` if out of bounds.
+    /// This takes time logarithmic in the number of chunks.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let chunk = self.ends.partition_point(|&end| end <= index);
+        let start = match chunk.checked_sub(1) {
+            Some(prev) => self.ends[prev],
+            None => 0,
+        };
+        self.chunk_slice(self.chunks.get(chunk)?).get(index - start)
+    }
+
+    /// Returns an iterator over all objects in the arena, in allocation order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| self.chunk_slice(chunk).iter())
+    }
+
+    fn chunk_slice<'a>(&'a self, chunk: &'a ArenaChunk<T>) -> &'a [T] {
+        // SAFETY: The first `entries` elements of the chunk have been initialized, and nothing
+        // can get mutable access to them anymore.
+        unsafe { slice::from_raw_parts(chunk.start(), chunk.entries) }
+    }
+}
+
+impl<T> Index<usize> for FrozenArena<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<T> Drop for FrozenArena<T> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.iter_mut().rev() {
+            // SAFETY: The chunk holds `entries` initialized objects.
+            unsafe { chunk.destroy(chunk.entries) };
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for FrozenArena<T> {}
+// SAFETY: `&FrozenArena<T>` only gives out `&T`.
+unsafe impl<T: Sync> Sync for FrozenArena<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for FrozenArena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Builds a slice in a `TypedArena` from objects that arrive one at a time, returned by
+/// `TypedArena::slice_builder`.
+///
+/// The objects are collected in a growing buffer on the heap and only moved into the arena by
+/// `finish`, for the reasons given in `TypedArena::try_alloc_from_iter`: other objects may be
+/// allocated in the arena meanwhile. If the builder is dropped without calling `finish`, the
+/// objects collected so far are dropped. After `finish`, the builder can be reused for the next
+/// slice, and keeps its buffer.
+pub struct SliceBuilder<'a, T> {
+    arena: &'a TypedArena<T>,
+    /// The objects that haven't been moved into the arena yet.
+    buf: Vec<T>,
+}
+
+impl<'a, T> SliceBuilder<'a, T> {
+    /// Appends an object to the slice being built.
+    #[inline]
+    pub fn push(&mut self, object: T) {
+        self.buf.push(object);
+    }
+
+    /// Returns the number of objects collected so far.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns whether no objects have been collected since the builder was created or last
+    /// finished.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Moves the collected objects into a contiguous slice in the arena, and returns it. Returns
+    /// an empty slice if no objects were collected. The builder is left empty.
+    #[track_caller]
+    pub fn finish(&mut self) -> &'a mut [T] {
+        assert!(size_of::<T>() != 0);
+        let len = self.buf.len();
+        if len == 0 {
+            return &mut [];
+        }
+        // SAFETY: As in `try_alloc_from_iter`, the storage is initialized by a copy that can't
+        // panic, and the stale objects in the buffer are forgotten.
+        unsafe {
+            let start_ptr = self.arena.alloc_raw_slice(len);
+            self.buf.as_ptr().copy_to_nonoverlapping(start_ptr, len);
+            self.buf.set_len(0);
+            slice::from_raw_parts_mut(start_ptr, len)
+        }
+    }
+}
+
+impl<T> Extend<T> for SliceBuilder<'_, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.buf.extend(iter);
+    }
+}
+
+/// An iterator that moves the objects out of a `TypedArena`, returned by `TypedArena::drain`.
+pub struct Drain<'a, T> {
+    /// The objects of each chunk, in allocation order.
+    slices: Vec<NonNull<[T]>>,
+    /// The index into `slices` of the chunk that the next object comes from.
+    chunk: usize,
+    /// The index of the next object in its chunk.
+    index: usize,
+    /// The storage of the objects is kept alive by the arena, which must not be used meanwhile.
+    _arena: PhantomData<&'a mut TypedArena<T>>,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let slice = self.slices.get(self.chunk)?;
+            if self.index < slice.len() {
+                // SAFETY: The object is initialized, and only read once, because `index` moves
+                // past it.
+                let object = unsafe { slice.cast::<T>().add(self.index).read() };
+                self.index += 1;
+                return Some(object);
+            }
+            self.chunk += 1;
+            self.index = 0;
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.slices.get(self.chunk..).map_or(0, |slices| {
+            slices.iter().map(|slice| slice.len()).sum::<usize>() - self.index
+        });
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for Drain<'_, T> {}
+
+impl<T> Drop for Drain<'_, T> {
+    fn drop(&mut self) {
+        // Drop the rest of the objects in reverse allocation order, like the arena would.
+        let Some(slices) = self.slices.get(self.chunk..) else {
+            return;
+        };
+        for (i, slice) in slices.iter().enumerate().rev() {
+            let start = if i == 0 { self.index } else { 0 };
+            for j in (start..slice.len()).rev() {
+                // SAFETY: The objects from `index` on haven't been yielded.
+                unsafe { slice.cast::<T>().add(j).drop_in_place() };
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for TypedArena<T> {
+    /// Prints a summary of the arena's memory use; the objects themselves are not printed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.stats();
+        f.debug_struct("TypedArena")
+            .field("chunk_count", &stats.chunk_count)
+            .field("bytes_used", &stats.bytes_used)
+            .field("bytes_reserved", &stats.bytes_reserved)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Poisons the used bytes of `chunks`, the last chunks of a `DroplessArena`. The last of them is
+/// the current chunk, whose free space ends at `end`.
+///
+/// # Safety
+///
+/// Nothing may read the objects in the chunks anymore.
+unsafe fn poison_dropless_chunks(chunks: &[ArenaChunk], end: *mut u8) {
+    if !POISONS {
+        return;
+    }
+    let last = chunks.len().wrapping_sub(1);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+        let used = if i == last {
+            // The last chunk's `entries` is not kept up to date.
+            used_bytes(chunk, end)
+        } else {
+            chunk.entries
+        };
+        // SAFETY: The used bytes lie within the chunk, and the caller guarantees that they
+        // aren't read anymore.
+        unsafe { poison(chunk.start().with_addr(chunk_end - used), used) };
+    }
+}
+
+/// Returns the number of bytes used in a `DroplessArena` chunk, given the end of its free space.
+#[inline]
+fn used_bytes(chunk: &ArenaChunk, end: *mut u8) -> usize {
+    align_down(chunk.end().addr(), DROPLESS_ALIGNMENT) - end.addr()
+}
+
 #[inline(always)]
 fn align_down(val: usize, align: usize) -> usize {
     debug_assert!(align.is_power_of_two());
@@ -417,6 +1692,76 @@ fn align_up(val: usize, align: usize) -> usize {
     (val + align - 1) & !(align - 1)
 }
 
+/// Panics because the size of a requested allocation or chunk exceeds `isize::MAX` bytes.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn capacity_overflow() -> ! {
+    panic!("arena allocation too large");
+}
+
+/// Like `capacity_overflow`, but names the requested size.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn allocation_too_large(bytes: usize) -> ! {
+    panic!("arena allocation too large: {bytes} bytes");
+}
+
+/// Like `capacity_overflow`, but names the requested number of objects of type `T` and their
+/// total size, which may not fit in a `usize`.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn array_too_large<T>(len: usize) -> ! {
+    panic!(
+        "arena allocation too large: {} bytes for {len} objects of type `{}`",
+        len as u128 * size_of::<T>() as u128,
+        type_name::<T>()
+    );
+}
+
+/// Returns the layout of an array of `len` objects of type `T`, panicking if it's too large.
+#[inline]
+#[track_caller]
+fn array_layout<T>(len: usize) -> Layout {
+    match Layout::array::<T>(len) {
+        Ok(layout) => layout,
+        Err(_) => array_too_large::<T>(len),
+    }
+}
+
+/// The byte that memory of destroyed objects is filled with if the `debug-checks` feature is
+/// enabled.
+const POISON: u8 = 0xDD;
+
+/// Whether `poison` does anything, i.e. whether freed memory needs to be visited at all.
+const POISONS: bool = cfg!(any(feature = "debug-checks", feature = "zeroize"));
+
+/// Fills `len` bytes at `start` with zeros if the `zeroize` feature is enabled, so that no
+/// sensitive data is left behind, or else with `POISON` if the `debug-checks` feature is enabled,
+/// so that dangling references into a cleared arena are easier to spot.
+///
+/// # Safety
+///
+/// `start` must be valid for writes of `len` bytes, and nothing may read those bytes as the
+/// objects that were there before.
+#[inline]
+unsafe fn poison(start: *mut u8, len: usize) {
+    if cfg!(feature = "zeroize") {
+        for i in 0..len {
+            // SAFETY: Guaranteed by the caller. The writes are volatile so that they can't be
+            // removed although the memory is freed or overwritten afterwards.
+            unsafe { start.add(i).write_volatile(0) };
+        }
+        // Keep later accesses to the memory from being reordered before the zeroing.
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+    } else if cfg!(feature = "debug-checks") {
+        // SAFETY: Guaranteed by the caller.
+        unsafe { start.write_bytes(POISON, len) };
+    }
+}
+
 // Pointer alignment is common in compiler types, so keep `DroplessArena` aligned to them
 // to optimize away alignment code.
 const DROPLESS_ALIGNMENT: usize = mem::align_of::<usize>();
@@ -439,257 +1784,2073 @@ pub struct DroplessArena {
 
     /// A vector of arena chunks.
     chunks: RefCell<Vec<ArenaChunk>>,
+
+    /// The number of allocations made since the arena was created.
+    allocations: Cell<u64>,
+
+    /// The value of `allocations` when the arena was last cleared.
+    allocations_at_clear: u64,
+
+    /// Determines the sizes of new chunks.
+    config: ArenaConfig,
 }
 
 unsafe impl Send for DroplessArena {}
 
+#[cfg(feature = "zeroize")]
+impl Drop for DroplessArena {
+    fn drop(&mut self) {
+        self.poison_chunks();
+    }
+}
+
+impl fmt::Debug for DroplessArena {
+    /// Prints a summary of the arena's memory use; the objects themselves are not printed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.stats();
+        f.debug_struct("DroplessArena")
+            .field("chunk_count", &stats.chunk_count)
+            .field("bytes_used", &stats.bytes_used)
+            .field("bytes_reserved", &stats.bytes_reserved)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Default for DroplessArena {
     #[inline]
     fn default() -> DroplessArena {
+        DroplessArena::with_config(ArenaConfig::default())
+    }
+}
+
+impl DroplessArena {
+    /// Creates a new `DroplessArena` whose chunks grow according to `config`.
+    pub fn with_config(config: ArenaConfig) -> DroplessArena {
         DroplessArena {
             // We set both `start` and `end` to 0 so that the first call to
             // alloc() will trigger a grow().
             start: Cell::new(ptr::null_mut()),
             end: Cell::new(ptr::null_mut()),
             chunks: Default::default(),
+            allocations: Cell::new(0),
+            allocations_at_clear: 0,
+            config,
         }
     }
-}
 
-impl DroplessArena {
+    /// Creates a new `DroplessArena` whose first chunk can hold at least `bytes` bytes.
+    ///
+    /// Later chunks keep doubling from the size of this one, as usual.
+    #[track_caller]
+    pub fn with_capacity(bytes: usize) -> DroplessArena {
+        let arena = DroplessArena::default();
+        if bytes != 0 {
+            match Layout::from_size_align(bytes, 1) {
+                Ok(layout) => arena.grow(layout),
+                Err(_) => allocation_too_large(bytes),
+            }
+        }
+        arena
+    }
+
     #[inline(never)]
     #[cold]
+    #[track_caller]
     fn grow(&self, layout: Layout) {
+        let new_cap = match self.new_chunk_capacity(layout) {
+            Some(new_cap) if new_cap <= isize::MAX as usize => new_cap,
+            _ => allocation_too_large(layout.size()),
+        };
+        // SAFETY: The capacity is non-zero.
+        self.push_chunk(unsafe { ArenaChunk::new(new_cap) });
+    }
+
+    /// Like `grow`, but returns `None` instead of aborting if the allocation fails.
+    #[inline(never)]
+    #[cold]
+    fn try_grow(&self, layout: Layout) -> Option<()> {
+        let new_cap = self.new_chunk_capacity(layout)?;
+        // Pushing the chunk must not fail either.
+        self.chunks.borrow_mut().try_reserve(1).ok()?;
+        // SAFETY: The capacity is non-zero.
+        self.push_chunk(unsafe { ArenaChunk::try_new(new_cap)? });
+        Some(())
+    }
+
+    /// Computes the size in bytes of the next chunk, which must fit an allocation with `layout`.
+    /// Returns `None` if that size overflows.
+    ///
+    /// This also records the number of used bytes in the current last chunk.
+    fn new_chunk_capacity(&self, layout: Layout) -> Option<usize> {
         // Add some padding so we can align `self.end` while
         // still fitting in a `layout` allocation.
-        let additional = layout.size() + cmp::max(DROPLESS_ALIGNMENT, layout.align()) - 1;
+        let additional = layout
+            .size()
+            .checked_add(cmp::max(DROPLESS_ALIGNMENT, layout.align()) - 1)?;
 
-        unsafe {
-            let mut chunks = self.chunks.borrow_mut();
-            let mut new_cap;
-            if let Some(last_chunk) = chunks.last_mut() {
-                // There is no need to update `last_chunk.entries` because that
-                // field isn't used by `DroplessArena`.
-
-                // If the previous chunk's len is less than HUGE_PAGE
-                // bytes, then this chunk will be least double the previous
-                // chunk's size.
-                new_cap = last_chunk.storage.len().min(HUGE_PAGE / 2);
-                new_cap *= 2;
-            } else {
-                new_cap = PAGE;
+        let mut chunks = self.chunks.borrow_mut();
+        let mut new_cap;
+        if let Some(last_chunk) = chunks.last_mut() {
+            // `DroplessArena` uses `entries` to record the number of used bytes instead.
+            last_chunk.entries = used_bytes(last_chunk, self.end.get());
+
+            new_cap = self.config.next_chunk_bytes(Some(last_chunk.storage.len()));
+        } else {
+            new_cap = self.config.next_chunk_bytes(None);
+        }
+        // Also ensure that this chunk can fit `additional`. Chunks that are sized for a
+        // single large allocation are rounded up to whole pages.
+        if additional > new_cap {
+            new_cap = additional.checked_next_multiple_of(PAGE)?;
+        }
+        Some(new_cap)
+    }
+
+    /// Makes `chunk` the chunk that is allocated into.
+    fn push_chunk(&self, chunk: ArenaChunk) {
+        let mut chunks = self.chunks.borrow_mut();
+        let chunk = chunks.push_mut(chunk);
+        self.start.set(chunk.start());
+
+        // Align the end to DROPLESS_ALIGNMENT.
+        let end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+
+        // Make sure we don't go past `start`. This should not happen since the allocation
+        // should be at least DROPLESS_ALIGNMENT - 1 bytes.
+        debug_assert!(chunk.start().addr() <= end);
+
+        self.end.set(chunk.end().with_addr(end));
+    }
+
+    /// Clears the arena. Deallocates all but the longest chunk, which is kept and reused by
+    /// subsequent allocations.
+    ///
+    /// Any references handed out before the call are invalidated; this is enforced by the
+    /// `&mut self` receiver.
+    pub fn clear(&mut self) {
+        self.clear_with(ClearPolicy::KeepLongest);
+    }
+
+    /// Clears the arena like `clear`, but `policy` decides which chunk, if any, is kept.
+    pub fn clear_with(&mut self, policy: ClearPolicy) {
+        self.poison_chunks();
+        self.allocations_at_clear = self.allocations.get();
+        let chunks = self.chunks.get_mut();
+        if !chunks.is_empty() {
+            match policy {
+                // The chunks that `absorb` moves in can be longer than the last chunk.
+                ClearPolicy::KeepLongest => keep_longest(chunks),
+                ClearPolicy::KeepFirst => chunks.truncate(1),
+                ClearPolicy::KeepNone => chunks.clear(),
+            }
+            let (start, end) = match chunks.first() {
+                Some(chunk) => {
+                    let end = chunk.end();
+                    (
+                        chunk.start(),
+                        end.with_addr(align_down(end.addr(), DROPLESS_ALIGNMENT)),
+                    )
+                }
+                None => (ptr::null_mut(), ptr::null_mut()),
+            };
+            self.start.set(start);
+            self.end.set(end);
+        }
+    }
+
+    /// Poisons the used bytes of all chunks, which are about to be freed or reused.
+    fn poison_chunks(&mut self) {
+        // SAFETY: The `&mut self` receiver ensures that no references to the objects are alive.
+        unsafe { poison_dropless_chunks(self.chunks.get_mut(), self.end.get()) };
+    }
+
+    /// Deallocates the chunks at the end of the arena that hold no objects, e.g. the chunk that
+    /// `clear` keeps around. If the arena is empty, this frees all of its memory.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Like [`shrink_to_fit`](Self::shrink_to_fit), but stops deallocating chunks once
+    /// `memory_reserved()` is at most `max_bytes`. This keeps some warm capacity after `clear`,
+    /// without holding on to a chunk that is larger than wanted.
+    ///
+    /// Only whole chunks are freed, and only those without objects, so the capacity can stay
+    /// above `max_bytes`, and it can end up well below it.
+    pub fn shrink_to(&mut self, max_bytes: usize) {
+        let mut reserved = self.memory_reserved();
+        let chunks = self.chunks.get_mut();
+        // Only the last chunk is tracked by `self.end`; the others know their used bytes.
+        while reserved > max_bytes
+            && chunks
+                .last()
+                .is_some_and(|last_chunk| used_bytes(last_chunk, self.end.get()) == 0)
+        {
+            let chunk = chunks.pop().unwrap();
+            reserved -= chunk.storage.len();
+            let (start, end) = match chunks.last() {
+                Some(chunk) => {
+                    let end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT) - chunk.entries;
+                    (chunk.start(), chunk.end().with_addr(end))
+                }
+                None => (ptr::null_mut(), ptr::null_mut()),
+            };
+            self.start.set(start);
+            self.end.set(end);
+        }
+    }
+
+    /// Moves the chunks of `other` into this arena, without copying the objects in them.
+    ///
+    /// The objects keep their addresses, so pointers to them stay valid for as long as this
+    /// arena keeps its chunks. Allocation continues in this arena's current chunk. The allocations
+    /// made in `other` are added to the counts of this arena.
+    pub fn absorb(&mut self, mut other: DroplessArena) {
+        self.allocations
+            .set(self.allocations.get() + other.allocations.get());
+        self.allocations_at_clear += other.allocations_at_clear;
+        let other_chunks = other.chunks.get_mut();
+        let Some(other_last) = other_chunks.last_mut() else {
+            return;
+        };
+        // Only the last chunk is tracked by `other.end`; the others know their used bytes.
+        other_last.entries = used_bytes(other_last, other.end.get());
+        let chunks = self.chunks.get_mut();
+        match chunks.len().checked_sub(1) {
+            Some(last) => drop(chunks.splice(last..last, other_chunks.drain(..))),
+            None => {
+                chunks.append(other_chunks);
+                self.start.set(other.start.get());
+                self.end.set(other.end.get());
             }
-            // Also ensure that this chunk can fit `additional`.
-            new_cap = cmp::max(additional, new_cap);
+        }
+    }
 
-            let chunk = chunks.push_mut(ArenaChunk::new(align_up(new_cap, PAGE)));
-            self.start.set(chunk.start());
+    /// Returns a checkpoint that [`rewind`](Self::rewind) can roll the arena back to.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            chunk_count: self.chunks.borrow().len(),
+            end: self.end.get().addr(),
+        }
+    }
+
+    /// Rolls the arena back to `checkpoint`, freeing everything that was allocated since then.
+    /// Chunks that were added after the checkpoint are deallocated.
+    ///
+    /// # Safety
+    ///
+    /// The references to objects allocated after the checkpoint become dangling, so they must
+    /// not be used anymore. `checkpoint` must have been taken from this arena, and the arena must
+    /// not have been cleared, shrunk or rewound to an earlier checkpoint since, nor have absorbed
+    /// another arena.
+    pub unsafe fn rewind(&self, checkpoint: Checkpoint) {
+        let mut chunks = self.chunks.borrow_mut();
+        assert!(checkpoint.chunk_count <= chunks.len(), "invalid checkpoint");
+        let added_chunks = chunks.len() > checkpoint.chunk_count;
+        // SAFETY: The chunks were added after the checkpoint, and the caller guarantees that their
+        // objects aren't used anymore.
+        unsafe { poison_dropless_chunks(&chunks[checkpoint.chunk_count..], self.end.get()) };
+        chunks.truncate(checkpoint.chunk_count);
+        let Some(chunk) = chunks.last() else {
+            self.start.set(ptr::null_mut());
+            self.end.set(ptr::null_mut());
+            return;
+        };
+        let chunk_end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+        let end = if added_chunks {
+            // The chunk recorded its used bytes when the next one was added.
+            chunk.end().with_addr(chunk_end - chunk.entries)
+        } else {
+            self.end.get()
+        };
+        assert!(
+            end.addr() <= checkpoint.end && checkpoint.end <= chunk_end,
+            "invalid checkpoint"
+        );
+        // SAFETY: The bytes were allocated after the checkpoint, and the caller guarantees that
+        // they aren't used anymore.
+        unsafe { poison(end, checkpoint.end - end.addr()) };
+        self.start.set(chunk.start());
+        self.end.set(end.with_addr(checkpoint.end));
+    }
+
+    /// Returns the allocated bytes of each chunk, in the order the chunks were added, as a
+    /// pointer to the first allocated byte and the number of allocated bytes.
+    ///
+    /// As the arena allocates downwards, the allocated bytes are at the end of each chunk. They
+    /// include the padding between allocations. This is meant for profiling and debugging tools;
+    /// the bytes may only be read through the pointers as long as nothing is allocated in them.
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (*const u8, usize)> {
+        (0..self.chunks.borrow().len()).map_while(move |i| {
+            let chunks = self.chunks.borrow();
+            let chunk = chunks.get(i)?;
+            let used = if i == chunks.len() - 1 {
+                // The last chunk's `entries` is not kept up to date.
+                used_bytes(chunk, self.end.get())
+            } else {
+                chunk.entries
+            };
+            let end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+            Some((chunk.start().with_addr(end - used).cast_const(), used))
+        })
+    }
+
+    /// Returns memory statistics for this arena. This takes time linear in the number of chunks.
+    pub fn stats(&self) -> ArenaStats {
+        let chunks = self.chunks.borrow();
+        let mut stats = ArenaStats {
+            chunk_count: chunks.len(),
+            ..ArenaStats::default()
+        };
+        for (i, chunk) in chunks.iter().enumerate() {
+            stats.bytes_reserved += chunk.storage.len();
+            stats.bytes_used += if i == chunks.len() - 1 {
+                // The last chunk's `entries` is not kept up to date.
+                used_bytes(chunk, self.end.get())
+            } else {
+                chunk.entries
+            };
+        }
+        stats.bytes_wasted = stats.bytes_reserved - stats.bytes_used;
+        stats
+    }
+
+    /// Returns the number of bytes taken up by allocated objects. This is the same as
+    /// `self.stats().bytes_used`.
+    pub fn memory_used(&self) -> usize {
+        self.stats().bytes_used
+    }
+
+    /// Returns the total capacity of all chunks, in bytes. This is the same as
+    /// `self.stats().bytes_reserved`.
+    pub fn memory_reserved(&self) -> usize {
+        self.chunks
+            .borrow()
+            .iter()
+            .map(|chunk| chunk.storage.len())
+            .sum::<usize>()
+    }
+
+    /// Returns the number of allocations that have been made in the arena since it was created.
+    /// This is not reset by `clear`.
+    ///
+    /// Every method that takes memory from the arena counts, i.e. every call of `alloc_raw`, which
+    /// the others are built on. Empty slices and strings don't take any memory, so they don't
+    /// count. Allocations that are rolled back by `rewind` still count.
+    pub fn total_allocations(&self) -> u64 {
+        self.allocations.get()
+    }
+
+    /// Returns the number of allocations that have been made in the arena since it was last
+    /// cleared. This counts like [`total_allocations`](Self::total_allocations).
+    pub fn allocations_since_clear(&self) -> u64 {
+        self.allocations.get() - self.allocations_at_clear
+    }
+
+    /// Returns whether `ptr` points into memory that has been allocated in this arena.
+    ///
+    /// Only the address is compared, so this also works for pointers to zero-sized types; those
+    /// are never returned by the arena, though. Free space at the end of a chunk doesn't count.
+    pub fn contains_ptr<T: ?Sized>(&self, ptr: *const T) -> bool {
+        let addr = ptr.addr();
+        let chunks = self.chunks.borrow();
+        chunks.iter().enumerate().any(|(i, chunk)| {
+            let end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+            let used = if i == chunks.len() - 1 {
+                // The last chunk's `entries` is not kept up to date.
+                used_bytes(chunk, self.end.get())
+            } else {
+                chunk.entries
+            };
+            (end - used..end).contains(&addr)
+        })
+    }
+
+    /// Ensures that at least `bytes` more bytes can be allocated without allocating a new chunk.
+    /// Unlike the allocation methods, this returns an error instead of aborting if allocating
+    /// the new chunk fails.
+    ///
+    /// Allocation sizes are rounded up to a multiple of `align_of::<usize>()` and aligned, which
+    /// takes up extra space, so the reservation should include some slack.
+    pub fn try_reserve(&self, bytes: usize) -> Result<(), AllocError> {
+        let available_bytes = self.end.get().addr() - self.start.get().addr();
+        if available_bytes >= bytes {
+            return Ok(());
+        }
+        let layout = Layout::from_size_align(bytes, 1).map_err(|_| AllocError)?;
+        self.try_grow(layout).ok_or(AllocError)
+    }
+
+    /// Allocates `layout.size()` bytes aligned to `layout.align()` in the `DroplessArena`,
+    /// returning a pointer to them. A new chunk is allocated if the current one is too small.
+    ///
+    /// This is the primitive that all other allocation methods are built on. The memory is
+    /// uninitialized and stays valid for as long as the arena is alive (or until it is cleared).
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-size layout
+    #[inline]
+    #[track_caller]
+    pub fn alloc_raw(&self, layout: Layout) -> NonNull<u8> {
+        assert!(layout.size() != 0);
+
+        // This loop executes once or twice: if allocation fails the first
+        // time, the `grow` ensures it will succeed the second time.
+        loop {
+            let start = self.start.get().addr();
+            let old_end = self.end.get();
+            let end = old_end.addr();
+
+            // Align allocated bytes so that `self.end` stays aligned to
+            // DROPLESS_ALIGNMENT.
+            let bytes = align_up(layout.size(), DROPLESS_ALIGNMENT);
+
+            // Tell LLVM that `end` is aligned to DROPLESS_ALIGNMENT.
+            unsafe { assert_unchecked(end == align_down(end, DROPLESS_ALIGNMENT)) };
+
+            if let Some(sub) = end.checked_sub(bytes) {
+                // `sub` is already aligned to DROPLESS_ALIGNMENT, which suffices for most types.
+                // The branch is usually resolved at compile time, as the layout is a constant
+                // after inlining.
+                let new_end = if layout.align() <= DROPLESS_ALIGNMENT {
+                    sub
+                } else {
+                    align_down(sub, layout.align())
+                };
+                if start <= new_end {
+                    let new_end = old_end.with_addr(new_end);
+                    // `new_end` is aligned to DROPLESS_ALIGNMENT as `align_down`
+                    // preserves alignment as both `end` and `bytes` are already
+                    // aligned to DROPLESS_ALIGNMENT.
+                    self.end.set(new_end);
+                    self.allocations.set(self.allocations.get() + 1);
+                    // SAFETY: `new_end` is at least `start`, which is never null here since the
+                    // arena has a chunk.
+                    return unsafe { NonNull::new_unchecked(new_end) };
+                }
+            }
+
+            // No free space left. Allocate a new chunk to satisfy the request.
+            // On failure the grow will panic or abort.
+            self.grow(layout);
+        }
+    }
+
+    /// Allocates `len` bytes aligned to `align` in the `DroplessArena`, returning them as a
+    /// mutable slice.
+    ///
+    /// The bytes are uninitialized, hence the `MaybeUninit<u8>` elements: fill them before
+    /// reading. `align` must be a power of two. Returns an empty slice if `len` is zero.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_bytes(&self, len: usize, align: usize) -> &mut [MaybeUninit<u8>] {
+        debug_assert!(align.is_power_of_two());
+        if len == 0 {
+            return &mut [];
+        }
+
+        let layout = Layout::from_size_align(len, align).unwrap();
+        let mem = self.alloc_raw(layout).as_ptr() as *mut MaybeUninit<u8>;
+
+        // SAFETY: `alloc_raw` returned `len` bytes that are exclusively ours. `MaybeUninit`
+        // elements may be uninitialized.
+        unsafe { slice::from_raw_parts_mut(mem, len) }
+    }
+
+    /// Allocates uninitialized storage for `len` objects in the `DroplessArena`, returning it as a
+    /// mutable slice.
+    ///
+    /// This lets the storage be filled incrementally, e.g. by a reader. Afterwards, the
+    /// initialized part can be turned into a `&mut [T]` with `slice[..n].assume_init_mut()`. It is
+    /// up to the caller to only do so for elements that have actually been written; reading an
+    /// uninitialized element is undefined behavior. Returns an empty slice if `len` is zero.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    #[inline]
+    #[track_caller]
+    pub fn alloc_uninit_slice<T: Copy>(&self, len: usize) -> &mut [MaybeUninit<T>] {
+        assert!(size_of::<T>() != 0);
+        if len == 0 {
+            return &mut [];
+        }
+
+        let layout = array_layout::<T>(len);
+        let mem = self.alloc_raw(layout).as_ptr() as *mut MaybeUninit<T>;
+
+        // SAFETY: `alloc_raw` returned storage for `len` objects that is exclusively ours.
+        // `MaybeUninit` elements may be uninitialized.
+        unsafe { slice::from_raw_parts_mut(mem, len) }
+    }
+
+    /// Allocates an object in the `DroplessArena`, returning a mutable reference to it.
+    ///
+    /// Panics:
+    ///
+    ///  - Types that need to be dropped, whose destructors would never run
+    ///  - Zero-sized types
+    #[inline]
+    #[track_caller]
+    pub fn alloc<T>(&self, object: T) -> &mut T {
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+
+        let mem = self.alloc_raw(Layout::new::<T>()).as_ptr() as *mut T;
+
+        unsafe {
+            // Write into uninitialized memory.
+            ptr::write(mem, object);
+            &mut *mem
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    /// Like [`alloc`](Self::alloc), but returns the object as an unsized type that it coerces
+    /// to, e.g. a trait object or a slice, without boxing it:
+    ///
+    /// ```rust
+    /// use std::fmt::Debug;
+    /// use stable_arena::DroplessArena;
+    ///
+    /// let arena = DroplessArena::default();
+    /// let x: &mut dyn Debug = arena.alloc_as(42);
+    /// assert_eq!(format!("{x:?}"), "42");
+    /// ```
+    ///
+    /// As with `alloc`, the object is never dropped, so `T` must not need to be dropped; the
+    /// panics are the same.
+    ///
+    /// This function is only available if the `nightly` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_as<'a, T, U>(&'a self, object: T) -> &'a mut U
+    where
+        T: core::marker::Unsize<U> + 'a,
+        U: ?Sized,
+    {
+        self.alloc::<T>(object)
+    }
+
+    /// Like [`alloc`](Self::alloc), but returns a shared reference, which can be copied freely.
+    /// This suits objects that are only mutated through interior mutability, e.g. to link them
+    /// up with `Cell`s.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_shared<T>(&self, object: T) -> &T {
+        self.alloc(object)
+    }
+
+    /// Like [`alloc`](Self::alloc), but returns the object pinned, for APIs that take a
+    /// `Pin<&mut T>`.
+    ///
+    /// Objects in an arena never move, so unlike `Box::pin` this costs nothing beyond `alloc`.
+    /// The memory is only reused after the arena is cleared or rewound, which needs the borrow
+    /// of the arena to end first; as `T: Copy` has no destructor, that is as good as dropping it.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_pinned<T: Copy>(&self, object: T) -> Pin<&mut T> {
+        // SAFETY: The object is never moved out of the arena, and its memory stays valid for as
+        // long as the returned reference lives.
+        unsafe { Pin::new_unchecked(self.alloc(object)) }
+    }
+
+    /// Allocates a default-initialized object in the `DroplessArena`, returning a mutable
+    /// reference to it.
+    ///
+    /// The slot is reserved before `T::default()` is called, so the object can be constructed
+    /// in place.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_default<T: Default + Copy>(&self) -> &mut T {
+        assert!(size_of::<T>() != 0);
+
+        let mem = self.alloc_raw(Layout::new::<T>()).as_ptr() as *mut T;
+
+        // SAFETY: The slot is valid for writes. If `T::default()` panics, the slot is simply
+        // left unused.
+        unsafe {
+            ptr::write(mem, T::default());
+            &mut *mem
+        }
+    }
+
+    /// Allocates a zero-initialized object in the `DroplessArena`, returning a mutable reference
+    /// to it.
+    ///
+    /// This avoids constructing a large zeroed value on the stack and moving it into the arena.
+    ///
+    /// # Safety
+    ///
+    /// The all-zero bit pattern must be a valid value of `T`. For example, this holds for
+    /// integers, floats and arrays of them, but not for references.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn alloc_zeroed<T: Copy>(&self) -> &mut T {
+        assert!(size_of::<T>() != 0);
+
+        let mem = self.alloc_raw(Layout::new::<T>()).as_ptr() as *mut T;
+
+        // SAFETY: The caller guarantees that zeroed memory is a valid `T`.
+        unsafe {
+            mem.write_bytes(0, 1);
+            &mut *mem
+        }
+    }
+
+    /// Allocates a zero-initialized slice of `len` objects in the `DroplessArena`, returning a
+    /// mutable reference to it. Returns an empty slice if `len` is zero.
+    ///
+    /// # Safety
+    ///
+    /// The all-zero bit pattern must be a valid value of `T`.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn alloc_slice_zeroed<T: Copy>(&self, len: usize) -> &mut [T] {
+        assert!(size_of::<T>() != 0);
+        if len == 0 {
+            return &mut [];
+        }
+
+        let mem = self.alloc_raw(array_layout::<T>(len)).as_ptr() as *mut T;
+
+        // SAFETY: The caller guarantees that zeroed memory is a valid `T`.
+        unsafe {
+            mem.write_bytes(0, len);
+            slice::from_raw_parts_mut(mem, len)
+        }
+    }
+
+    /// Allocates a slice of objects that are copied into the `DroplessArena`, returning a mutable
+    /// reference to it. Will panic if passed a zero-sized type.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    ///  - Zero-length slices
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice<T>(&self, slice: &[T]) -> &mut [T]
+    where
+        T: Copy,
+    {
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+        assert!(!slice.is_empty());
+
+        let mem = self.alloc_raw(Layout::for_value::<[T]>(slice)).as_ptr() as *mut T;
+
+        unsafe {
+            mem.copy_from_nonoverlapping(slice.as_ptr(), slice.len());
+            slice::from_raw_parts_mut(mem, slice.len())
+        }
+    }
+
+    /// Copies the elements of `vec` into the `DroplessArena` and frees the `Vec`, returning a
+    /// mutable reference to the copy. Returns an empty slice if `vec` is empty.
+    ///
+    /// The arena can't take over the `Vec`'s allocation, so this always copies; it is a
+    /// convenience for callers that are done with the `Vec`.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_from_vec<T: Copy>(&self, vec: Vec<T>) -> &mut [T] {
+        assert!(size_of::<T>() != 0);
+        if vec.is_empty() {
+            return &mut [];
+        }
+        self.alloc_slice(&vec)
+    }
+
+    /// Like [`alloc_slice`](Self::alloc_slice), but returns the copy as a raw pointer and its
+    /// length, e.g. for passing it through FFI.
+    ///
+    /// The pointer isn't tied to a borrow of the arena, but it is only valid as long as the
+    /// arena is neither dropped nor cleared.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    ///  - Zero-length slices
+    #[inline]
+    #[track_caller]
+    pub fn alloc_raw_slice<T: Copy>(&self, slice: &[T]) -> (NonNull<T>, usize) {
+        let len = slice.len();
+        (NonNull::from(self.alloc_slice(slice)).cast(), len)
+    }
+
+    /// Like [`alloc_slice`](Self::alloc_slice), but the copy starts at an address that is a
+    /// multiple of `align`, e.g. for use with SIMD instructions.
+    ///
+    /// Panics:
+    ///
+    ///  - `align` is not a power of two, or smaller than `align_of::<T>()`
+    ///  - Empty slice
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_aligned<T: Copy>(&self, slice: &[T], align: usize) -> &mut [T] {
+        assert!(align.is_power_of_two() && align >= align_of::<T>());
+        assert!(size_of::<T>() != 0);
+        assert!(!slice.is_empty());
+
+        let layout = Layout::from_size_align(size_of_val(slice), align)
+            .unwrap_or_else(|_| capacity_overflow());
+        let mem = self.alloc_raw(layout).as_ptr() as *mut T;
+
+        // SAFETY: `alloc_raw` returned storage for `slice.len()` objects that is exclusively ours.
+        unsafe {
+            mem.copy_from_nonoverlapping(slice.as_ptr(), slice.len());
+            slice::from_raw_parts_mut(mem, slice.len())
+        }
+    }
+
+    /// Allocates a slice of `len` objects in the `DroplessArena`, initializing the element at
+    /// index `i` with `f(i)`, and returns a mutable reference to it. Returns an empty slice if
+    /// `len` is zero.
+    ///
+    /// The storage is reserved up front and the elements are written in place.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_fill_with<T, F>(&self, len: usize, mut f: F) -> &mut [T]
+    where
+        T: Copy,
+        F: FnMut(usize) -> T,
+    {
+        assert!(size_of::<T>() != 0);
+        if len == 0 {
+            return &mut [];
+        }
+
+        let mem = self.alloc_raw(array_layout::<T>(len)).as_ptr() as *mut T;
+
+        // SAFETY: `mem` is valid for `len` writes. If `f` panics, the storage simply stays
+        // unused, which is fine because `T` is `Copy` and therefore not dropped.
+        unsafe {
+            for i in 0..len {
+                mem.add(i).write(f(i));
+            }
+            slice::from_raw_parts_mut(mem, len)
+        }
+    }
+
+    /// Allocates a slice of `len` copies of `value` in the `DroplessArena`, returning a mutable
+    /// reference to it. Returns an empty slice if `len` is zero.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_fill_copy<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        self.alloc_slice_fill_with(len, |_| value)
+    }
+
+    /// Allocates a slice of `len` default values in the `DroplessArena`, returning a mutable
+    /// reference to it. Returns an empty slice if `len` is zero.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_default<T: Copy + Default>(&self, len: usize) -> &mut [T] {
+        self.alloc_slice_fill_copy(len, T::default())
+    }
+
+    /// Allocates the concatenation of `parts` as one contiguous slice in the `DroplessArena`,
+    /// returning a mutable reference to it. Returns an empty slice if all parts are empty.
+    ///
+    /// The storage is reserved once and the parts are copied into it directly.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_concat<T: Copy>(&self, parts: &[&[T]]) -> &mut [T] {
+        let len = parts
+            .iter()
+            .try_fold(0usize, |len, part| len.checked_add(part.len()))
+            .unwrap_or_else(|| capacity_overflow());
+        let uninit = self.alloc_uninit_slice::<T>(len);
+        let mut mem = uninit.as_mut_ptr() as *mut T;
+        for part in parts {
+            // SAFETY: The parts fit into the `len` elements of storage and don't overlap it.
+            unsafe {
+                mem.copy_from_nonoverlapping(part.as_ptr(), part.len());
+                mem = mem.add(part.len());
+            }
+        }
+        // SAFETY: All `len` elements have been initialized.
+        unsafe { uninit.assume_init_mut() }
+    }
+
+    /// Allocates an array that is copied into the `DroplessArena`, returning a mutable reference
+    /// to it. Unlike `alloc`, this also accepts empty arrays.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_array<T: Copy, const N: usize>(&self, array: [T; N]) -> &mut [T; N] {
+        self.alloc_slice_fill_with(N, |i| array[i])
+            .try_into()
+            .unwrap()
+    }
+
+    /// Allocates a string slice that is copied into the `DroplessArena`, returning a
+    /// reference to it. Will panic if passed an empty string.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-length string
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str(&self, string: &str) -> &str {
+        self.alloc_str_mut(string)
+    }
+
+    /// Copies each of `strs` into the `DroplessArena`, and returns a slice of the copies that is
+    /// allocated in the arena, too. Unlike with `alloc_str`, empty strings are allowed.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_strs<'a, I: IntoIterator<Item = &'a str>>(&self, strs: I) -> &mut [&str] {
+        self.alloc_slice_from_iter(strs.into_iter().map(|string| {
+            if string.is_empty() {
+                ""
+            } else {
+                self.alloc_str(string)
+            }
+        }))
+    }
+
+    /// Like [`alloc_strs`](Self::alloc_strs), but copies all of `strs` into one contiguous
+    /// region, followed by a single allocation for the slice of references into it. This keeps
+    /// e.g. the strings of a symbol table close together. Empty strings are allowed.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str_list(&self, strs: &[&str]) -> &[&str] {
+        let len = strs
+            .iter()
+            .try_fold(0usize, |len, string| len.checked_add(string.len()))
+            .unwrap_or_else(|| capacity_overflow());
+        let bytes = self.alloc_uninit_slice::<u8>(len);
+        let mut mem = bytes.as_mut_ptr() as *mut u8;
+        let list = self.alloc_uninit_slice::<&str>(strs.len());
+        for (slot, string) in list.iter_mut().zip(strs) {
+            // SAFETY: The strings fit into the `len` bytes of storage and don't overlap it. Each
+            // copy is valid UTF-8, and no other reference to its bytes is ever handed out.
+            unsafe {
+                mem.copy_from_nonoverlapping(string.as_ptr(), string.len());
+                let copy = slice::from_raw_parts(mem, string.len());
+                slot.write(core::str::from_utf8_unchecked(copy));
+                mem = mem.add(string.len());
+            }
+        }
+        // SAFETY: All elements of `list` have been initialized.
+        unsafe { list.assume_init_ref() }
+    }
+
+    /// Validates that `bytes` are UTF-8 and copies them into the `DroplessArena` as a string slice.
+    /// Returns an empty string if `bytes` is empty.
+    ///
+    /// Starting from a `&str`, use `alloc_str` instead, which doesn't validate the bytes again.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str_from_bytes(&self, bytes: &[u8]) -> Result<&str, Utf8Error> {
+        let string = core::str::from_utf8(bytes)?;
+        if string.is_empty() {
+            return Ok("");
+        }
+        Ok(self.alloc_str(string))
+    }
+
+    /// Like [`alloc_str`](Self::alloc_str), but returns a mutable reference to the copy, which can
+    /// then be modified in place (e.g. with [`str::make_ascii_lowercase`]).
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-length string
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str_mut(&self, string: &str) -> &mut str {
+        let slice = self.alloc_slice(string.as_bytes());
+
+        // SAFETY: the result has a copy of the same valid UTF-8 bytes.
+        unsafe { core::str::from_utf8_unchecked_mut(slice) }
+    }
+
+    /// Formats `args` directly into the `DroplessArena`, like `format!` but without a temporary
+    /// `String`, and returns the resulting string.
+    ///
+    /// ```rust
+    /// use stable_arena::DroplessArena;
+    ///
+    /// let arena = DroplessArena::default();
+    /// let (line, column) = (3, 14);
+    /// assert_eq!(arena.alloc_fmt(format_args!("{line}:{column}")), "3:14");
+    /// ```
+    ///
+    /// Panics:
+    ///
+    ///  - A formatting trait implementation returns an error
+    #[track_caller]
+    pub fn alloc_fmt(&self, args: fmt::Arguments<'_>) -> &str {
+        let mut buf = ByteBuf::new(self);
+        fmt::write(&mut buf, args)
+            .expect("a formatting trait implementation returned an error when the buffer did not");
+        // SAFETY: Only UTF-8 strings have been written into the buffer.
+        unsafe { core::str::from_utf8_unchecked(buf.finish()) }
+    }
+
+    /// Copies `string` into the `DroplessArena`, appends a NUL terminator, and returns the result
+    /// as a C string.
+    ///
+    /// Panics:
+    ///
+    ///  - `string` contains a NUL byte
+    #[inline]
+    #[track_caller]
+    pub fn alloc_c_str(&self, string: &str) -> &CStr {
+        match self.try_alloc_c_str(string) {
+            Ok(c_str) => c_str,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Like [`alloc_c_str`](Self::alloc_c_str), but returns an error instead of panicking if
+    /// `string` contains a NUL byte. Nothing is allocated in that case.
+    #[inline]
+    #[track_caller]
+    pub fn try_alloc_c_str(&self, string: &str) -> Result<&CStr, NulError> {
+        let bytes = string.as_bytes();
+        if let Some(position) = bytes.iter().position(|&b| b == 0) {
+            return Err(NulError { position });
+        }
+        // A `str` is at most `isize::MAX` bytes long, so this doesn't overflow.
+        let len = bytes.len() + 1;
+        let mem = self.alloc_raw(array_layout::<u8>(len));
+        // SAFETY: `alloc_raw` returned `len` bytes that are exclusively ours, and `bytes` can't
+        // overlap them. Writing the terminator initializes the last one.
+        let slice = unsafe {
+            mem.copy_from_nonoverlapping(NonNull::from(bytes).cast(), bytes.len());
+            mem.add(bytes.len()).write(0);
+            slice::from_raw_parts(mem.as_ptr(), len)
+        };
+        // SAFETY: the slice ends in its only NUL byte.
+        Ok(unsafe { CStr::from_bytes_with_nul_unchecked(slice) })
+    }
+
+    /// Allocates the elements of an iterator that is expected to yield `len` elements into a
+    /// contiguous slice in the `DroplessArena`. `T` must not need to be dropped.
+    #[inline]
+    #[track_caller]
+    fn alloc_exact_from_iter<T, I: Iterator<Item = T>>(&self, iter: I, len: usize) -> &mut [T] {
+        if len == 0 {
+            return &mut [];
+        }
+
+        let mem = self.alloc_raw(array_layout::<T>(len)).as_ptr() as *mut T;
+        // SAFETY: `write_from_iter` doesn't touch `self`. It only touches the slice we just
+        // reserved. If the iterator panics or doesn't output `len` elements, this will leave some
+        // unallocated slots in the arena, which is fine because we do not call `drop`.
+        unsafe { self.write_from_iter(iter, len, mem) }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that `mem` is valid for writes up to `size_of::<T>() * len`, and that
+    /// that memory stays allocated and not shared for the lifetime of `self`. This must hold even
+    /// if `iter.next()` allocates onto `self`.
+    #[inline]
+    unsafe fn write_from_iter<T, I: Iterator<Item = T>>(
+        &self,
+        mut iter: I,
+        len: usize,
+        mem: *mut T,
+    ) -> &mut [T] {
+        let mut i = 0;
+        // Use a manual loop since LLVM manages to optimize it better for
+        // slice iterators
+        loop {
+            // SAFETY: The caller must ensure that `mem` is valid for writes up to
+            // `size_of::<T>() * len`.
+            unsafe {
+                match iter.next() {
+                    Some(value) if i < len => mem.add(i).write(value),
+                    Some(_) | None => {
+                        // We only return as many items as the iterator gave us, even
+                        // though it was supposed to give us `len`
+                        return slice::from_raw_parts_mut(mem, i);
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    /// Allocates the elements of this iterator of `Copy` values into a contiguous slice in the
+    /// `DroplessArena`.
+    ///
+    /// If the iterator reports an exact length through its `size_hint`, as an
+    /// `ExactSizeIterator` does, the elements are written directly into the arena. Otherwise
+    /// they are collected into a `Vec` first. Unlike `alloc_from_iter`, this is available without
+    /// the `from-iter` feature.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice_from_iter<T: Copy, I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
+        assert!(size_of::<T>() != 0);
+
+        // Warning: this function is reentrant: `iter` could hold a reference to `&self` and
+        // allocate additional elements while we're iterating.
+        let iter = iter.into_iter();
+        match iter.size_hint() {
+            (min, Some(max)) if min == max => self.alloc_exact_from_iter(iter, min),
+            (_, _) => outline(move || {
+                let vec: Vec<T> = iter.collect();
+                if vec.is_empty() {
+                    &mut []
+                } else {
+                    self.alloc_slice(&vec)
+                }
+            }),
+        }
+    }
+
+    #[cfg(feature = "from-iter")]
+    /// Allocates the elements of this iterator into a contiguous slice in the `DroplessArena`.
+    ///
+    /// Note: for reasons of reentrancy and panic safety we collect into a `SmallVec<[_; 8]>` before
+    /// storing the elements in the arena.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_iter<T, I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+
+        // Warning: this function is reentrant: `iter` could hold a reference to `&self` and
+        // allocate additional elements while we're iterating.
+        let iter = iter.into_iter();
+
+        let size_hint = iter.size_hint();
+
+        match size_hint {
+            (min, Some(max)) if min == max => {
+                // We know the exact number of elements the iterator expects to produce here.
+                self.alloc_exact_from_iter(iter, min)
+            }
+            (_, _) => outline(move || {
+                // Note: `Infallible` stands in for the never type `!` used upstream (see the README).
+                match self.try_alloc_from_iter(iter.map(Ok::<T, Infallible>)) {
+                    Ok(slice) => slice,
+                    Err(never) => match never {},
+                }
+            }),
+        }
+    }
+
+    #[cfg(feature = "from-iter")]
+    /// Allocates the elements of this iterator into a contiguous slice in the `DroplessArena`.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn try_alloc_from_iter<T, E>(
+        &self,
+        iter: impl IntoIterator<Item = Result<T, E>>,
+    ) -> Result<&mut [T], E> {
+        // Despite the similarity with `alloc_from_iter`, we cannot reuse their fast case, as we
+        // cannot know the minimum length of the iterator in this case.
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+
+        // Takes care of reentrancy.
+        let mut vec = try_collect(iter)?;
+        if vec.is_empty() {
+            return Ok(&mut []);
+        }
+        // Move the content to the arena by copying and then forgetting it.
+        let len = vec.len();
+        Ok(unsafe {
+            let start_ptr = self
+                .alloc_raw(Layout::for_value::<[T]>(vec.as_slice()))
+                .as_ptr() as *mut T;
+            vec.as_ptr().copy_to_nonoverlapping(start_ptr, len);
+            vec.set_len(0);
+            slice::from_raw_parts_mut(start_ptr, len)
+        })
+    }
+
+    #[cfg(feature = "from-iter")]
+    /// Like [`alloc_from_iter`](Self::alloc_from_iter), but fails if the iterator yields more
+    /// than `max_elems` elements, e.g. because its length is controlled by untrusted input.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_iter_capped<T, I: IntoIterator<Item = T>>(
+        &self,
+        iter: I,
+        max_elems: usize,
+    ) -> Result<&mut [T], CapExceeded> {
+        self.try_alloc_from_iter(cap_iter(iter, max_elems))
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// Lets a `DroplessArena` back collections that are generic over an allocator.
+///
+/// Memory is carved out of the arena with the requested layout, and is only freed when the arena
+/// is dropped: `deallocate` does nothing.
+///
+/// This impl is only available if the `nightly` feature is enabled.
+unsafe impl core::alloc::Allocator for &DroplessArena {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let ptr = if layout.size() == 0 {
+            // `alloc_raw` doesn't support zero-sized allocations.
+            // SAFETY: The alignment is non-zero.
+            unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) }
+        } else {
+            self.alloc_raw(layout)
+        };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // The arena frees all memory at once when it is dropped.
+    }
+}
+
+/// A byte buffer that is built up incrementally in a `DroplessArena`, for when the total length
+/// isn't known in advance.
+///
+/// The buffer grows in place while it is the most recent allocation of the arena and the current
+/// chunk has room. Otherwise, it is copied to a larger allocation.
+pub struct ByteBuf<'a> {
+    arena: &'a DroplessArena,
+    /// The start of the buffer's allocation, or dangling if `cap` is zero.
+    ptr: NonNull<u8>,
+    /// The number of bytes in the buffer.
+    len: usize,
+    /// The size of the buffer's allocation, a multiple of `DROPLESS_ALIGNMENT`.
+    cap: usize,
+    /// The start of the chunk that the buffer was allocated in. A later chunk can end right
+    /// where the buffer starts, so `ptr` alone doesn't tell whether the buffer is in the current
+    /// chunk.
+    chunk_start: *mut u8,
+}
+
+impl<'a> ByteBuf<'a> {
+    /// Creates an empty buffer in `arena`. This doesn't allocate.
+    pub fn new(arena: &'a DroplessArena) -> ByteBuf<'a> {
+        ByteBuf {
+            arena,
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap: 0,
+            chunk_start: ptr::null_mut(),
+        }
+    }
+
+    /// Appends `bytes` to the buffer.
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        let len = self
+            .len
+            .checked_add(bytes.len())
+            .unwrap_or_else(|| capacity_overflow());
+        if len > self.cap {
+            self.grow(len);
+        }
+        // SAFETY: The allocation has room for `len` bytes.
+        unsafe {
+            self.ptr
+                .add(self.len)
+                .copy_from_nonoverlapping(NonNull::from(bytes).cast(), bytes.len());
+        }
+        self.len = len;
+    }
+
+    /// Returns the number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bytes of the buffer, which stay in the arena. If the buffer is still the most
+    /// recent allocation, the space it didn't use is given back to the arena.
+    pub fn finish(self) -> &'a mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        let mut start = self.ptr.as_ptr();
+        if self.is_last_allocation() {
+            // The arena allocates downwards, so move the bytes to the end of the allocation and
+            // give back the space before them.
+            let new_start = align_down(start.addr() + self.cap - self.len, DROPLESS_ALIGNMENT);
+            start = start.with_addr(new_start);
+            // SAFETY: Both ranges lie within the allocation.
+            unsafe { ptr::copy(self.ptr.as_ptr(), start, self.len) };
+            self.arena.end.set(start);
+        }
+        // SAFETY: The bytes are initialized and owned by the buffer, which is consumed.
+        unsafe { slice::from_raw_parts_mut(start, self.len) }
+    }
+
+    /// Returns whether the buffer's allocation is in the arena's current chunk, directly after
+    /// its free space.
+    fn is_last_allocation(&self) -> bool {
+        self.cap != 0
+            && self.arena.start.get() == self.chunk_start
+            && self.arena.end.get() == self.ptr.as_ptr()
+    }
+
+    /// Makes room for at least `len` bytes.
+    #[cold]
+    fn grow(&mut self, len: usize) {
+        let new_cap = cmp::max(len, self.cap.saturating_mul(2));
+        let new_cap = cmp::max(new_cap, 8 * DROPLESS_ALIGNMENT)
+            .checked_next_multiple_of(DROPLESS_ALIGNMENT)
+            .unwrap_or_else(|| capacity_overflow());
+        let extra = new_cap - self.cap;
+        if self.is_last_allocation()
+            && self.arena.end.get().addr() - self.arena.start.get().addr() >= extra
+        {
+            // Take the free space before the buffer and move the bytes there.
+            let start = self.arena.end.get().wrapping_sub(extra);
+            // SAFETY: Both ranges lie within the current chunk, and `start` is free.
+            unsafe { ptr::copy(self.ptr.as_ptr(), start, self.len) };
+            self.arena.end.set(start);
+            // SAFETY: `start` is not null, since it is within a chunk.
+            self.ptr = unsafe { NonNull::new_unchecked(start) };
+        } else {
+            let layout =
+                Layout::from_size_align(new_cap, 1).unwrap_or_else(|_| capacity_overflow());
+            let start = self.arena.alloc_raw(layout);
+            // SAFETY: The new allocation is distinct from the old one and has room for `len`
+            // bytes.
+            unsafe { start.copy_from_nonoverlapping(self.ptr, self.len) };
+            self.ptr = start;
+            self.chunk_start = self.arena.start.get();
+        }
+        self.cap = new_cap;
+    }
+}
+
+/// Appends the UTF-8 bytes of the formatted text, so that `write!` can fill the buffer, e.g. as
+/// in [`DroplessArena::alloc_fmt`].
+impl fmt::Write for ByteBuf<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// A type-erased destructor for an object in a `DropArena`.
+struct DropType {
+    drop_fn: unsafe fn(*mut u8),
+    obj: *mut u8,
+}
+
+/// Drops the `T` that `to_drop` points to.
+///
+/// # Safety
+///
+/// `to_drop` must point to a valid `T` that is not used afterwards.
+unsafe fn drop_for_type<T>(to_drop: *mut u8) {
+    unsafe { ptr::drop_in_place(to_drop as *mut T) }
+}
+
+impl Drop for DropType {
+    fn drop(&mut self) {
+        unsafe { (self.drop_fn)(self.obj) }
+    }
+}
+
+/// An arena that can hold objects of multiple different types, including types that need to
+/// be dropped.
+///
+/// The objects are stored in a backing `DroplessArena`, and a destructor is recorded for each
+/// object that needs to be dropped. When the arena is dropped, the destructors run in reverse
+/// allocation order.
+///
+/// Objects may borrow data for `'a`, which must outlive the arena.
+///
+/// As the destructor is recorded for the concrete type, objects can be used as trait objects
+/// without boxing them, and are still dropped properly:
+///
+/// ```rust
+/// use stable_arena::{DroplessArena, DropArena};
+///
+/// trait Named {
+///     fn name(&self) -> &str;
+/// }
+/// struct Dog(String);
+/// impl Named for Dog {
+///     fn name(&self) -> &str {
+///         &self.0
+///     }
+/// }
+///
+/// let arena = DropArena::default();
+/// let dog: &mut dyn Named = arena.alloc(Dog(String::from("Scooter")));
+/// assert_eq!(dog.name(), "Scooter");
+/// ```
+#[derive(Default)]
+pub struct DropArena<'a> {
+    /// Destructors of the allocated objects, in allocation order.
+    destructors: RefCell<Vec<DropType>>,
+
+    /// The backing storage of the objects.
+    arena: DroplessArena,
+
+    /// `'a` is invariant, so that it cannot be shortened to admit objects that don't outlive
+    /// the arena.
+    _marker: PhantomData<fn(&'a ()) -> &'a ()>,
+}
+
+impl<'a> DropArena<'a> {
+    /// Allocates an object in the `DropArena`, returning a mutable reference to it.
+    #[inline]
+    #[track_caller]
+    pub fn alloc<T: 'a>(&self, object: T) -> &mut T {
+        let mem = if size_of::<T>() == 0 {
+            NonNull::<T>::dangling().as_ptr()
+        } else {
+            self.arena.alloc_raw(Layout::new::<T>()).as_ptr() as *mut T
+        };
+
+        unsafe {
+            // Write into uninitialized memory.
+            ptr::write(mem, object);
+            if mem::needs_drop::<T>() {
+                self.destructors.borrow_mut().push(DropType {
+                    drop_fn: drop_for_type::<T>,
+                    obj: mem as *mut u8,
+                });
+            }
+            &mut *mem
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    /// Like [`alloc`](Self::alloc), but returns the object as an unsized type that it coerces
+    /// to, e.g. a trait object. The object is dropped with the arena as its concrete type.
+    ///
+    /// This is only needed in generic code: otherwise, the `&mut T` that `alloc` returns can be
+    /// coerced directly.
+    ///
+    /// This function is only available if the `nightly` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_as<T, U>(&self, object: T) -> &mut U
+    where
+        T: core::marker::Unsize<U> + 'a,
+        U: ?Sized,
+    {
+        self.alloc::<T>(object)
+    }
+
+    #[cfg(feature = "from-iter")]
+    /// Allocates the elements of this iterator into a contiguous slice in the `DropArena`.
+    ///
+    /// Note: for reasons of reentrancy and panic safety we collect into a `SmallVec<[_; 8]>` before
+    /// storing the elements in the arena.
+    ///
+    /// This function is only available if the `from-iter` feature is enabled.
+    #[inline]
+    #[track_caller]
+    pub fn alloc_from_iter<T: 'a, I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
+        let mut vec: SmallVec<[T; 8]> = iter.into_iter().collect();
+        if vec.is_empty() {
+            return &mut [];
+        }
+        let len = vec.len();
+        let start_ptr = if size_of::<T>() == 0 {
+            NonNull::<T>::dangling().as_ptr()
+        } else {
+            self.arena
+                .alloc_raw(Layout::for_value::<[T]>(vec.as_slice()))
+                .as_ptr() as *mut T
+        };
+
+        unsafe {
+            // Move the content to the arena by copying and then forgetting it.
+            vec.as_ptr().copy_to_nonoverlapping(start_ptr, len);
+            vec.set_len(0);
+            if mem::needs_drop::<T>() {
+                let mut destructors = self.destructors.borrow_mut();
+                destructors.reserve(len);
+                for i in 0..len {
+                    destructors.push(DropType {
+                        drop_fn: drop_for_type::<T>,
+                        obj: start_ptr.add(i) as *mut u8,
+                    });
+                }
+            }
+            slice::from_raw_parts_mut(start_ptr, len)
+        }
+    }
+}
+
+impl DropArena<'_> {
+    /// Clears the arena, running the destructors of all objects in reverse allocation order.
+    ///
+    /// The backing `DroplessArena` is cleared as well, keeping its longest chunk for reuse.
+    pub fn clear(&mut self) {
+        self.run_destructors();
+        self.arena.clear();
+    }
+
+    /// Runs the destructors in reverse allocation order.
+    fn run_destructors(&mut self) {
+        let destructors = self.destructors.get_mut();
+        while let Some(destructor) = destructors.pop() {
+            drop(destructor);
+        }
+    }
+}
+
+impl fmt::Debug for DropArena<'_> {
+    /// Prints the number of objects with destructors and a summary of the arena's memory use.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DropArena")
+            .field("destructors", &self.destructors.borrow().len())
+            .field("arena", &self.arena)
+            .finish()
+    }
+}
+
+impl Drop for DropArena<'_> {
+    fn drop(&mut self) {
+        // `DroplessArena` then frees the memory.
+        self.run_destructors();
+    }
+}
+
+/// A chunk of a `SyncDroplessArena`, linked to the chunk that was current before it.
+struct SyncChunk {
+    /// The raw storage for the chunk.
+    storage: ArenaChunk,
+
+    /// A pointer to the start of the storage.
+    start: *mut u8,
+
+    /// A pointer to the end of free space. As in `DroplessArena`, allocation proceeds downwards
+    /// and this is kept aligned to DROPLESS_ALIGNMENT.
+    end: AtomicPtr<u8>,
+
+    /// The chunk that was current before this one, or null.
+    prev: *mut SyncChunk,
+}
+
+/// A `DroplessArena` that can be shared between threads.
+///
+/// Allocation is lock-free: each allocation carves its region out of the current chunk with a
+/// compare-and-swap on the chunk's bump pointer. When the current chunk is full, a new chunk is
+/// allocated and installed with another compare-and-swap; if several threads race to grow the
+/// arena, one of them wins and the others free their chunk and retry in the winner's.
+///
+/// As for `DroplessArena`, the objects are not dropped.
+pub struct SyncDroplessArena {
+    /// The chunk to allocate from, or null if no chunk has been allocated yet. All other chunks
+    /// are reachable through the `prev` links.
+    current: AtomicPtr<SyncChunk>,
+}
+
+unsafe impl Send for SyncDroplessArena {}
+// SAFETY: Threads only share the chunks' bump pointers, which are atomic. Every allocation hands
+// out a region that no other allocation overlaps.
+unsafe impl Sync for SyncDroplessArena {}
+
+impl Default for SyncDroplessArena {
+    #[inline]
+    fn default() -> SyncDroplessArena {
+        SyncDroplessArena {
+            current: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl SyncDroplessArena {
+    /// Replaces `old` as the current chunk with a new chunk that can fit `layout`, unless
+    /// another thread has already done so.
+    #[inline(never)]
+    #[cold]
+    #[track_caller]
+    fn grow(&self, old: *mut SyncChunk, layout: Layout) {
+        if self.current.load(Ordering::Relaxed) != old {
+            // Another thread grew the arena in the meantime.
+            return;
+        }
+
+        // Add some padding so we can align `end` while still fitting in a `layout` allocation.
+        let additional = layout
+            .size()
+            .checked_add(cmp::max(DROPLESS_ALIGNMENT, layout.align()) - 1)
+            .unwrap_or_else(|| capacity_overflow());
+        let mut new_cap = if old.is_null() {
+            PAGE
+        } else {
+            // SAFETY: Chunks are only freed when the arena is dropped.
+            let prev_cap = unsafe { (*old).storage.storage.len() };
+            prev_cap.min(HUGE_PAGE / 2) * 2
+        };
+        new_cap = cmp::max(additional, new_cap)
+            .checked_next_multiple_of(PAGE)
+            .unwrap_or_else(|| capacity_overflow());
+
+        let storage: ArenaChunk = unsafe { ArenaChunk::new(new_cap) };
+        let start = storage.start();
+        let end = storage.end();
+        // Align the end to DROPLESS_ALIGNMENT.
+        let end = end.with_addr(align_down(end.addr(), DROPLESS_ALIGNMENT));
+        let chunk = Box::into_raw(Box::new(SyncChunk {
+            storage,
+            start,
+            end: AtomicPtr::new(end),
+            prev: old,
+        }));
+
+        // `Release` makes the new chunk's fields visible to threads that load it with `Acquire`.
+        if self
+            .current
+            .compare_exchange(old, chunk, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            // Another thread won the race; allocate from its chunk instead.
+            drop(unsafe { Box::from_raw(chunk) });
+        }
+    }
+
+    /// Allocates `layout.size()` bytes aligned to `layout.align()` in the `SyncDroplessArena`,
+    /// returning a pointer to them. A new chunk is installed if the current one is too small.
+    ///
+    /// Like `DroplessArena::alloc_raw`, this is the primitive that all other allocation methods
+    /// are built on. The size is rounded up to a multiple of the alignment of `usize`, and the
+    /// memory is uninitialized and stays valid for as long as the arena is alive.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-size layout
+    #[inline]
+    #[track_caller]
+    pub fn alloc_raw(&self, layout: Layout) -> NonNull<u8> {
+        assert!(layout.size() != 0);
+
+        // Align allocated bytes so that `end` stays aligned to DROPLESS_ALIGNMENT.
+        let bytes = align_up(layout.size(), DROPLESS_ALIGNMENT);
+
+        loop {
+            // `Acquire` pairs with the `Release` in `grow`, so the chunk's fields are initialized.
+            let chunk = self.current.load(Ordering::Acquire);
+            // SAFETY: Chunks are only freed when the arena is dropped.
+            if let Some(chunk) = unsafe { chunk.as_ref() } {
+                // The bump pointer doesn't publish any data, it only hands out disjoint regions,
+                // so `Relaxed` is enough for it.
+                let mut old_end = chunk.end.load(Ordering::Relaxed);
+                while let Some(sub) = old_end.addr().checked_sub(bytes) {
+                    let new_end = align_down(sub, layout.align());
+                    if new_end < chunk.start.addr() {
+                        break;
+                    }
+                    let new_end = old_end.with_addr(new_end);
+                    match chunk.end.compare_exchange_weak(
+                        old_end,
+                        new_end,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        // SAFETY: `new_end` is at least the start of the chunk, which is not null.
+                        Ok(_) => return unsafe { NonNull::new_unchecked(new_end) },
+                        Err(actual) => old_end = actual,
+                    }
+                }
+            }
+
+            // No free space left. Install a new chunk and try again.
+            self.grow(chunk, layout);
+        }
+    }
+
+    /// Allocates an object in the `SyncDroplessArena`, returning a mutable reference to it.
+    ///
+    /// Panics:
+    ///
+    ///  - Types that need to be dropped, whose destructors would never run
+    ///  - Zero-sized types
+    #[inline]
+    #[track_caller]
+    pub fn alloc<T>(&self, object: T) -> &mut T {
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+
+        let mem = self.alloc_raw(Layout::new::<T>()).as_ptr() as *mut T;
+
+        unsafe {
+            // Write into uninitialized memory.
+            ptr::write(mem, object);
+            &mut *mem
+        }
+    }
+
+    /// Allocates a slice of objects that are copied into the `SyncDroplessArena`, returning a
+    /// mutable reference to it.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-sized types
+    ///  - Zero-length slices
+    #[inline]
+    #[track_caller]
+    pub fn alloc_slice<T>(&self, slice: &[T]) -> &mut [T]
+    where
+        T: Copy,
+    {
+        assert!(!mem::needs_drop::<T>());
+        assert!(size_of::<T>() != 0);
+        assert!(!slice.is_empty());
+
+        let mem = self.alloc_raw(Layout::for_value::<[T]>(slice)).as_ptr() as *mut T;
+
+        unsafe {
+            mem.copy_from_nonoverlapping(slice.as_ptr(), slice.len());
+            slice::from_raw_parts_mut(mem, slice.len())
+        }
+    }
+
+    /// Allocates a string slice that is copied into the `SyncDroplessArena`, returning a
+    /// reference to it.
+    ///
+    /// Panics:
+    ///
+    ///  - Zero-length string
+    #[inline]
+    #[track_caller]
+    pub fn alloc_str(&self, string: &str) -> &str {
+        let slice = self.alloc_slice(string.as_bytes());
+
+        // SAFETY: the result has a copy of the same valid UTF-8 bytes.
+        unsafe { core::str::from_utf8_unchecked(slice) }
+    }
+}
+
+impl Drop for SyncDroplessArena {
+    fn drop(&mut self) {
+        let mut chunk = *self.current.get_mut();
+        while !chunk.is_null() {
+            // SAFETY: Every chunk was created by `Box::into_raw` and is linked exactly once.
+            let mut boxed = unsafe { Box::from_raw(chunk) };
+            let end = *boxed.end.get_mut();
+            let chunk_end = align_down(boxed.storage.end().addr(), DROPLESS_ALIGNMENT);
+            // SAFETY: The bytes from `end` to the aligned end of the chunk were allocated, and
+            // the `&mut self` receiver ensures that no references to them are alive.
+            unsafe { poison(end, chunk_end - end.addr()) };
+            chunk = boxed.prev;
+        }
+    }
+}
+
+/// A string interner backed by a `DroplessArena`.
+///
+/// Interning equal strings returns the same arena-backed `&str` each time, so interned strings
+/// can be compared by pointer.
+#[derive(Default)]
+pub struct StrInterner {
+    /// The strings interned so far.
+    ///
+    /// The `'static` lifetime is a lie: the strings live in `arena`. They are never handed out
+    /// with that lifetime, only reborrowed for the lifetime of `&self`.
+    strings: RefCell<BTreeSet<&'static str>>,
+
+    /// The storage of the interned strings.
+    arena: DroplessArena,
+}
+
+impl StrInterner {
+    /// Interns a string, returning the arena-backed copy that is shared by all equal strings.
+    pub fn intern(&self, string: &str) -> &str {
+        if let Some(&interned) = self.strings.borrow().get(string) {
+            return interned;
+        }
+        if string.is_empty() {
+            return "";
+        }
+
+        // SAFETY: The string lives in `self.arena`, which is never cleared, so it stays valid for
+        // as long as `self`. See `strings` for why `'static` is fine.
+        let interned: &'static str = unsafe { &*(self.arena.alloc_str(string) as *const str) };
+        self.strings.borrow_mut().insert(interned);
+        interned
+    }
+}
+
+/// A slice interner backed by a `DroplessArena`.
+///
+/// Like `StrInterner`, but for slices of any `Copy` element type, e.g. lists of type arguments.
+/// Equal slices are deduplicated, so interned slices can be compared by pointer. The elements
+/// need to be `Ord` because `no_std` doesn't provide a hash set.
+///
+/// Slices are only ever compared with `Ord`, never as raw bytes, so element types with padding
+/// (whose bytes may be uninitialized) are fine.
+pub struct SliceInterner<T> {
+    /// The slices interned so far.
+    slices: RefCell<BTreeSet<InternedSlice<T>>>,
+
+    /// The storage of the interned slices.
+    arena: DroplessArena,
+}
+
+// SAFETY: The interned slices are owned by the interner, so it may be sent to another thread if
+// the elements may.
+unsafe impl<T: Send> Send for SliceInterner<T> {}
+
+/// A slice in the arena of a `SliceInterner`, compared by its contents.
+///
+/// This is a raw pointer rather than a `&'static [T]` so that `T` doesn't need to be `'static`.
+struct InternedSlice<T>(NonNull<[T]>);
+
+impl<T> InternedSlice<T> {
+    fn get(&self) -> &[T] {
+        // SAFETY: The slice lives in the interner's arena, which is never cleared, and the
+        // interner only hands out shared references to it.
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<T: Ord> PartialEq for InternedSlice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<T: Ord> Eq for InternedSlice<T> {}
+
+impl<T: Ord> PartialOrd for InternedSlice<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for InternedSlice<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.get().cmp(other.get())
+    }
+}
+
+impl<T> Borrow<[T]> for InternedSlice<T> {
+    fn borrow(&self) -> &[T] {
+        self.get()
+    }
+}
+
+impl<T> Default for SliceInterner<T> {
+    fn default() -> SliceInterner<T> {
+        SliceInterner {
+            slices: Default::default(),
+            arena: DroplessArena::default(),
+        }
+    }
+}
+
+impl<T: Copy + Ord> SliceInterner<T> {
+    /// Interns a slice, returning the arena-backed copy that is shared by all equal slices.
+    ///
+    /// All empty slices are interned as the same `&[]`.
+    pub fn intern_slice(&self, slice: &[T]) -> &[T] {
+        if slice.is_empty() {
+            return &[];
+        }
+        if let Some(interned) = self.slices.borrow().get(slice) {
+            // SAFETY: See `InternedSlice::get`; the slice lives as long as `self`.
+            return unsafe { interned.0.as_ref() };
+        }
+
+        let interned = NonNull::from(&*self.arena.alloc_slice(slice));
+        self.slices.borrow_mut().insert(InternedSlice(interned));
+        // SAFETY: As above.
+        unsafe { interned.as_ref() }
+    }
+}
+
+/// A handle to an object in an `IndexedArena`.
+///
+/// Unlike a reference, an `Idx` doesn't borrow the arena, so it can be stored freely. Passing it
+/// to an arena other than the one that returned it gives an unspecified object or panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Idx(u32);
+
+impl Idx {
+    /// Returns the position of the object in allocation order, starting at zero.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A `TypedArena` that hands out indices instead of references.
+///
+/// This is convenient for graphs and trees whose nodes refer to each other: an `Idx` is `Copy`
+/// and doesn't borrow the arena.
+pub struct IndexedArena<T> {
+    arena: TypedArena<T>,
+
+    /// The index of the first object in each chunk of `arena`.
+    chunk_starts: RefCell<Vec<u32>>,
+
+    /// The number of objects in the arena.
+    len: Cell<u32>,
+}
+
+impl<T> Default for IndexedArena<T> {
+    fn default() -> IndexedArena<T> {
+        IndexedArena {
+            arena: TypedArena::default(),
+            chunk_starts: Default::default(),
+            len: Cell::new(0),
+        }
+    }
+}
+
+impl<T> IndexedArena<T> {
+    /// Allocates an object in the `IndexedArena`, returning its index.
+    ///
+    /// Panics if the arena already holds `u32::MAX` objects.
+    pub fn push(&self, value: T) -> Idx {
+        let idx = self.len.get();
+        let len = idx
+            .checked_add(1)
+            .expect("too many objects in `IndexedArena`");
+        self.arena.alloc(value);
+        // Objects are allocated one at a time, so every chunk but the last one is full, and a new
+        // chunk starts exactly at the object that didn't fit in the previous one.
+        let mut chunk_starts = self.chunk_starts.borrow_mut();
+        if self.arena.chunks.borrow().len() > chunk_starts.len() {
+            chunk_starts.push(idx);
+        }
+        self.len.set(len);
+        Idx(idx)
+    }
+
+    /// Returns a reference to the object with the given index.
+    ///
+    /// Panics if there is no such object.
+    pub fn get(&self, idx: Idx) -> &T {
+        // SAFETY: The object has been initialized, and the arena never hands out mutable
+        // references to it while `self` is shared.
+        unsafe { &*self.slot(idx) }
+    }
+
+    /// Returns a mutable reference to the object with the given index.
+    ///
+    /// Panics if there is no such object.
+    pub fn get_mut(&mut self, idx: Idx) -> &mut T {
+        // SAFETY: The object has been initialized, and `&mut self` ensures that no other
+        // references to it exist.
+        unsafe { &mut *self.slot(idx) }
+    }
+
+    /// Returns an iterator over the objects in the arena and their indices, in allocation order.
+    pub fn iter_indexed(&self) -> impl Iterator<Item = (Idx, &T)> {
+        (0..self.len.get()).map(|i| (Idx(i), self.get(Idx(i))))
+    }
+
+    /// Returns a pointer to the object with the given index.
+    fn slot(&self, idx: Idx) -> *mut T {
+        assert!(idx.0 < self.len.get(), "index out of bounds");
+        let chunk_starts = self.chunk_starts.borrow();
+        let chunk = chunk_starts.partition_point(|&start| start <= idx.0) - 1;
+        let offset = (idx.0 - chunk_starts[chunk]) as usize;
+        // SAFETY: Every chunk holds the objects up to the start of the next one.
+        unsafe { self.arena.chunks.borrow()[chunk].start().add(offset) }
+    }
+
+    /// Returns the number of objects in the arena.
+    pub fn len(&self) -> usize {
+        self.len.get() as usize
+    }
+
+    /// Returns whether the arena is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+}
+
+impl<T> Index<Idx> for IndexedArena<T> {
+    type Output = T;
+
+    fn index(&self, idx: Idx) -> &T {
+        self.get(idx)
+    }
+}
 
-            // Align the end to DROPLESS_ALIGNMENT.
-            let end = align_down(chunk.end().addr(), DROPLESS_ALIGNMENT);
+impl<T> IndexMut<Idx> for IndexedArena<T> {
+    fn index_mut(&mut self, idx: Idx) -> &mut T {
+        self.get_mut(idx)
+    }
+}
 
-            // Make sure we don't go past `start`. This should not happen since the allocation
-            // should be at least DROPLESS_ALIGNMENT - 1 bytes.
-            debug_assert!(chunk.start().addr() <= end);
+/// The lock of the arena pools, which only hold it to push or pop an arena. This is a
+/// `std::sync::Mutex` if the `std` feature is enabled.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct PoolLock<T> {
+    value: std::sync::Mutex<T>,
+}
 
-            self.end.set(chunk.end().with_addr(end));
-        }
+#[cfg(feature = "std")]
+impl<T> PoolLock<T> {
+    /// Calls `f` with exclusive access to the value.
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        // A panic while the lock is held can't leave the list of arenas half-modified.
+        let mut value = self
+            .value
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(&mut value)
     }
+}
 
-    #[inline]
-    pub fn alloc_raw(&self, layout: Layout) -> *mut u8 {
-        assert!(layout.size() != 0);
+/// The lock of the arena pools, which only hold it to push or pop an arena. Without the `std`
+/// feature, there is no `Mutex`, so this is a spin lock.
+#[cfg(not(feature = "std"))]
+#[derive(Default)]
+struct PoolLock<T> {
+    locked: core::sync::atomic::AtomicBool,
+    value: core::cell::UnsafeCell<T>,
+}
 
-        // This loop executes once or twice: if allocation fails the first
-        // time, the `grow` ensures it will succeed the second time.
-        loop {
-            let start = self.start.get().addr();
-            let old_end = self.end.get();
-            let end = old_end.addr();
+// SAFETY: The lock ensures that only one thread at a time accesses the value.
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send> Sync for PoolLock<T> {}
 
-            // Align allocated bytes so that `self.end` stays aligned to
-            // DROPLESS_ALIGNMENT.
-            let bytes = align_up(layout.size(), DROPLESS_ALIGNMENT);
+#[cfg(not(feature = "std"))]
+impl<T> PoolLock<T> {
+    /// Calls `f` with exclusive access to the value.
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait until the lock looks free before trying to take it again.
+            while self.locked.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
 
-            // Tell LLVM that `end` is aligned to DROPLESS_ALIGNMENT.
-            unsafe { assert_unchecked(end == align_down(end, DROPLESS_ALIGNMENT)) };
+        /// Releases the lock, also if `f` panics.
+        struct Unlock<'a>(&'a core::sync::atomic::AtomicBool);
 
-            if let Some(sub) = end.checked_sub(bytes) {
-                let new_end = align_down(sub, layout.align());
-                if start <= new_end {
-                    let new_end = old_end.with_addr(new_end);
-                    // `new_end` is aligned to DROPLESS_ALIGNMENT as `align_down`
-                    // preserves alignment as both `end` and `bytes` are already
-                    // aligned to DROPLESS_ALIGNMENT.
-                    self.end.set(new_end);
-                    return new_end;
-                }
+        impl Drop for Unlock<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Release);
             }
-
-            // No free space left. Allocate a new chunk to satisfy the request.
-            // On failure the grow will panic or abort.
-            self.grow(layout);
         }
-    }
 
-    /// Allocates an object in the `DroplessArena`, returning a mutable reference to it.
-    #[inline]
-    pub fn alloc<T>(&self, object: T) -> &mut T {
-        assert!(!mem::needs_drop::<T>());
-        assert!(size_of::<T>() != 0);
+        let _unlock = Unlock(&self.locked);
+        // SAFETY: We hold the lock.
+        f(unsafe { &mut *self.value.get() })
+    }
+}
 
-        let mem = self.alloc_raw(Layout::new::<T>()) as *mut T;
+/// A pool of `TypedArena`s that are cleared and reused instead of being dropped.
+///
+/// This suits workloads that use one arena per request, for example: a reused arena keeps its
+/// largest chunk (see `TypedArena::clear`), so it doesn't have to allocate new chunks for every
+/// request. The pool can be shared between threads.
+pub struct ArenaPool<T> {
+    arenas: PoolLock<Vec<TypedArena<T>>>,
+}
 
-        unsafe {
-            // Write into uninitialized memory.
-            ptr::write(mem, object);
-            &mut *mem
+impl<T> Default for ArenaPool<T> {
+    fn default() -> ArenaPool<T> {
+        ArenaPool {
+            arenas: PoolLock::default(),
         }
     }
+}
 
-    /// Allocates a slice of objects that are copied into the `DroplessArena`, returning a mutable
-    /// reference to it. Will panic if passed a zero-sized type.
-    ///
-    /// Panics:
-    ///
-    ///  - Zero-sized types
-    ///  - Zero-length slices
-    #[inline]
-    pub fn alloc_slice<T>(&self, slice: &[T]) -> &mut [T]
-    where
-        T: Copy,
-    {
-        assert!(!mem::needs_drop::<T>());
-        assert!(size_of::<T>() != 0);
-        assert!(!slice.is_empty());
-
-        let mem = self.alloc_raw(Layout::for_value::<[T]>(slice)) as *mut T;
-
-        unsafe {
-            mem.copy_from_nonoverlapping(slice.as_ptr(), slice.len());
-            slice::from_raw_parts_mut(mem, slice.len())
+impl<T> ArenaPool<T> {
+    /// Takes an arena out of the pool, or creates a new one if the pool is empty. The arena is
+    /// cleared and returned to the pool when the guard is dropped.
+    pub fn checkout(&self) -> PooledArena<'_, T> {
+        PooledArena {
+            arena: self.arenas.with(|arenas| arenas.pop()).unwrap_or_default(),
+            pool: self,
         }
     }
+}
 
-    /// Allocates a string slice that is copied into the `DroplessArena`, returning a
-    /// reference to it. Will panic if passed an empty string.
-    ///
-    /// Panics:
-    ///
-    ///  - Zero-length string
-    #[inline]
-    pub fn alloc_str(&self, string: &str) -> &str {
-        let slice = self.alloc_slice(string.as_bytes());
+/// A `TypedArena` checked out of an `ArenaPool`.
+pub struct PooledArena<'a, T> {
+    arena: TypedArena<T>,
+    pool: &'a ArenaPool<T>,
+}
 
-        // SAFETY: the result has a copy of the same valid UTF-8 bytes.
-        unsafe { core::str::from_utf8_unchecked(slice) }
+impl<T> Deref for PooledArena<'_, T> {
+    type Target = TypedArena<T>;
+
+    fn deref(&self) -> &TypedArena<T> {
+        &self.arena
     }
+}
 
-    #[cfg(feature = "from-iter")]
-    /// # Safety
-    ///
-    /// The caller must ensure that `mem` is valid for writes up to `size_of::<T>() * len`, and that
-    /// that memory stays allocated and not shared for the lifetime of `self`. This must hold even
-    /// if `iter.next()` allocates onto `self`.
-    #[inline]
-    unsafe fn write_from_iter<T, I: Iterator<Item = T>>(
-        &self,
-        mut iter: I,
-        len: usize,
-        mem: *mut T,
-    ) -> &mut [T] {
-        let mut i = 0;
-        // Use a manual loop since LLVM manages to optimize it better for
-        // slice iterators
-        loop {
-            // SAFETY: The caller must ensure that `mem` is valid for writes up to
-            // `size_of::<T>() * len`.
-            unsafe {
-                match iter.next() {
-                    Some(value) if i < len => mem.add(i).write(value),
-                    Some(_) | None => {
-                        // We only return as many items as the iterator gave us, even
-                        // though it was supposed to give us `len`
-                        return slice::from_raw_parts_mut(mem, i);
-                    }
-                }
-            }
-            i += 1;
-        }
+impl<T> DerefMut for PooledArena<'_, T> {
+    fn deref_mut(&mut self) -> &mut TypedArena<T> {
+        &mut self.arena
     }
+}
 
-    #[cfg(feature = "from-iter")]
-    /// Allocates the elements of this iterator into a contiguous slice in the `DroplessArena`.
-    ///
-    /// Note: for reasons of reentrancy and panic safety we collect into a `SmallVec<[_; 8]>` before
-    /// storing the elements in the arena.
-    ///
-    /// This function is only available if the `from-iter` feature is enabled.
-    #[inline]
-    pub fn alloc_from_iter<T, I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
-        assert!(!mem::needs_drop::<T>());
-        assert!(size_of::<T>() != 0);
+impl<T> Drop for PooledArena<'_, T> {
+    fn drop(&mut self) {
+        self.arena.clear();
+        let arena = mem::take(&mut self.arena);
+        self.pool.arenas.with(|arenas| arenas.push(arena));
+    }
+}
 
-        // Warning: this function is reentrant: `iter` could hold a reference to `&self` and
-        // allocate additional elements while we're iterating.
-        let iter = iter.into_iter();
+/// A pool of `DroplessArena`s that are cleared and reused instead of being dropped. See
+/// `ArenaPool`.
+#[derive(Default)]
+pub struct DroplessArenaPool {
+    arenas: PoolLock<Vec<DroplessArena>>,
+}
 
-        let size_hint = iter.size_hint();
+impl DroplessArenaPool {
+    /// Takes an arena out of the pool, or creates a new one if the pool is empty. The arena is
+    /// cleared and returned to the pool when the guard is dropped.
+    pub fn checkout(&self) -> PooledDroplessArena<'_> {
+        PooledDroplessArena {
+            arena: self.arenas.with(|arenas| arenas.pop()).unwrap_or_default(),
+            pool: self,
+        }
+    }
+}
 
-        match size_hint {
-            (min, Some(max)) if min == max => {
-                // We know the exact number of elements the iterator expects to produce here.
-                let len = min;
+/// A `DroplessArena` checked out of a `DroplessArenaPool`.
+pub struct PooledDroplessArena<'a> {
+    arena: DroplessArena,
+    pool: &'a DroplessArenaPool,
+}
 
-                if len == 0 {
-                    return &mut [];
-                }
+impl Deref for PooledDroplessArena<'_> {
+    type Target = DroplessArena;
 
-                let mem = self.alloc_raw(Layout::array::<T>(len).unwrap()) as *mut T;
-                // SAFETY: `write_from_iter` doesn't touch `self`. It only touches the slice we just
-                // reserved. If the iterator panics or doesn't output `len` elements, this will
-                // leave some unallocated slots in the arena, which is fine because we do not call
-                // `drop`.
-                unsafe { self.write_from_iter(iter, len, mem) }
-            }
-            (_, _) => outline(move || {
-                // Note: `Infallible` stands in for the never type `!` used upstream (see the README).
-                match self.try_alloc_from_iter(iter.map(Ok::<T, Infallible>)) {
-                    Ok(slice) => slice,
-                    Err(never) => match never {},
-                }
-            }),
-        }
+    fn deref(&self) -> &DroplessArena {
+        &self.arena
     }
+}
 
-    #[cfg(feature = "from-iter")]
-    /// Allocates the elements of this iterator into a contiguous slice in the `DroplessArena`.
-    ///
-    /// This function is only available if the `from-iter` feature is enabled.
-    #[inline]
-    pub fn try_alloc_from_iter<T, E>(
-        &self,
-        iter: impl IntoIterator<Item = Result<T, E>>,
-    ) -> Result<&mut [T], E> {
-        // Despite the similarity with `alloc_from_iter`, we cannot reuse their fast case, as we
-        // cannot know the minimum length of the iterator in this case.
-        assert!(!mem::needs_drop::<T>());
-        assert!(size_of::<T>() != 0);
+impl DerefMut for PooledDroplessArena<'_> {
+    fn deref_mut(&mut self) -> &mut DroplessArena {
+        &mut self.arena
+    }
+}
 
-        // Takes care of reentrancy.
-        let vec: Result<SmallVec<[T; 8]>, E> = iter.into_iter().collect();
-        let mut vec = vec?;
-        if vec.is_empty() {
-            return Ok(&mut []);
-        }
-        // Move the content to the arena by copying and then forgetting it.
-        let len = vec.len();
-        Ok(unsafe {
-            let start_ptr = self.alloc_raw(Layout::for_value::<[T]>(vec.as_slice())) as *mut T;
-            vec.as_ptr().copy_to_nonoverlapping(start_ptr, len);
-            vec.set_len(0);
-            slice::from_raw_parts_mut(start_ptr, len)
-        })
+impl Drop for PooledDroplessArena<'_> {
+    fn drop(&mut self) {
+        self.arena.clear();
+        let arena = mem::take(&mut self.arena);
+        self.pool.arenas.with(|arenas| arenas.push(arena));
     }
 }
 
@@ -712,6 +3873,11 @@ pub struct IsNotCopy;
 /// - Types that are `!Copy` and `Drop`: these must be specified in the
 ///   arguments. The `TypedArena` will be used for them.
 ///
+/// Field types may be generic and may use the arena's lifetime parameter, e.g.
+/// `Vec<Box<i32>>` or `&'a [Foo]`. A `Copy` type like the latter need not be listed, but if it
+/// is, both the `Copy` and the listed impl apply to it, so `alloc` must be told which one to
+/// use: `arena.alloc::<_, IsCopy>(value)`. Either way it ends up in the `DroplessArena`.
+///
 /// # Usage
 ///
 /// ```rust
@@ -729,51 +3895,137 @@ pub struct IsNotCopy;
 /// assert_eq!(c.get(), 1);
 /// let b = arena.alloc(Box::new(2));
 /// assert_eq!(**b, 2);
+/// // Slices of `Copy` types can be copied in, those of any type cloned.
+/// assert_eq!(arena.alloc_slice(&[1, 2]), &[1, 2]);
+/// assert_eq!(arena.alloc_slice_clone(&[Box::new(3)]), &[Box::new(3)]);
+/// assert_eq!(arena.alloc_slice_clone(&[4, 5]), &[4, 5]);
+/// ```
+///
+/// # Visibility
+///
+/// By default, `Arena` and `ArenaAllocatable` are `pub`. A different visibility can be given
+/// before the list, e.g. `declare_arena!(pub(crate) [...])`, or `pub(self)` to make them private.
+///
+/// # Naming the arena
+///
+/// The struct can be given a different name, optionally with a lifetime parameter that the
+/// field types may use. Declaring several arenas in one scope also requires renaming the trait,
+/// which follows a colon:
+///
+/// ```rust
+/// use stable_arena::declare_arena;
+///
+/// declare_arena!(StrArena<'a>: StrAllocatable [
+///     refs: Vec<&'a str>,
+/// ]);
+/// declare_arena!(pub(crate) IntArena: IntAllocatable [
+///     boxes: Box<i32>,
+/// ]);
+///
+/// let ints = IntArena::default();
+/// let strs = StrArena::default();
+/// let v = strs.alloc(vec!["a", "b"]);
+/// assert_eq!(v, &["a", "b"]);
+/// assert_eq!(**ints.alloc(Box::new(1)), 1);
+/// ```
+///
+/// # Routing unlisted types through a `DropArena`
+///
+/// An optional field name after the list adds a `DropArena` field of that name, together with
+/// an `alloc_any`/`alloc_any_from_iter` pair of methods that accept any `'static` type. Types
+/// that need to be dropped go to the `DropArena` and all others to the `DroplessArena`, so
+/// `Drop` types don't need a `TypedArena` of their own.
+///
+/// ```rust
+/// use stable_arena::declare_arena;
+///
+/// declare_arena!([], drops);
+///
+/// let arena = Arena::default();
+/// let s: &mut String = arena.alloc_any(String::from("hello"));
+/// assert_eq!(s, "hello");
+/// ```
+///
+/// # Attributes
+///
+/// Attributes before everything else, including doc comments, are put on the struct. `Default`
+/// is always derived, so it must not be listed again.
+///
+/// ```rust
+/// use stable_arena::declare_arena;
+///
+/// declare_arena!(
+///     /// The arena for boxes.
+///     #[derive(Debug)]
+///     BoxArena: BoxAllocatable [
+///         boxes: Box<i32>,
+///     ]
+/// );
+///
+/// let arena = BoxArena::default();
+/// arena.alloc(Box::new(1));
+/// assert!(format!("{arena:?}").starts_with("BoxArena {"));
 /// ```
 ///
 /// This macro is only available if the `from-iter` feature is enabled.
 #[macro_export]
 macro_rules! declare_arena {
-    ([$($name:ident: $ty:ty,)*]) => {
+    // Generates the code, once the optional parts of the input have been filled in. `$impl_lt`
+    // is a lifetime that the impls of the trait can be generic over: the arena's own if it has
+    // one, and an unused one otherwise.
+    (@impl [$($attrs:tt)*] ($vis:vis) $arena:ident [$($lt:lifetime)?] $impl_lt:lifetime,
+     $arena_ty:ty, $trait:ident, $copy_trait:path, $not_copy_trait:path,
+     [$($name:ident: $ty:ty,)*] [$($drop:ident)?]) => {
+        $($attrs)*
         #[derive(Default)]
-        pub struct Arena {
+        $vis struct $arena$(<$lt>)? {
             pub dropless: $crate::DroplessArena,
+            $(pub $drop: $crate::DropArena<'static>,)?
             $($name: $crate::TypedArena<$ty>,)*
+            $(_marker: ::core::marker::PhantomData<&$lt ()>,)?
         }
 
-        pub trait ArenaAllocatable<C = $crate::IsNotCopy>: Sized {
-            #[allow(clippy::mut_from_ref)]
-            fn allocate_on(self, arena: &Arena) -> &mut Self;
-            #[allow(clippy::mut_from_ref)]
-            fn allocate_from_iter(
-                arena: &Arena,
+        #[allow(dead_code)]
+        $vis trait $trait<$($lt,)? C = $crate::IsNotCopy>: Sized {
+            #[track_caller]
+            #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+            fn allocate_on<'b>(self, arena: &'b $arena_ty) -> &'b mut Self;
+            #[track_caller]
+            #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+            fn allocate_from_iter<'b>(
+                arena: &'b $arena_ty,
                 iter: impl ::core::iter::IntoIterator<Item = Self>,
-            ) -> &mut [Self];
+            ) -> &'b mut [Self];
         }
 
         // Any type that impls `Copy` can be arena-allocated in the `DroplessArena`.
-        impl<T: Copy> ArenaAllocatable<$crate::IsCopy> for T {
+        #[allow(clippy::extra_unused_lifetimes)]
+        impl<$impl_lt, T: Copy> $copy_trait for T {
             #[inline]
-            #[allow(clippy::mut_from_ref)]
-            fn allocate_on(self, arena: &Arena) -> &mut Self {
+            #[track_caller]
+            #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+            fn allocate_on<'b>(self, arena: &'b $arena_ty) -> &'b mut Self {
                 arena.dropless.alloc(self)
             }
 
             #[inline]
-            #[allow(clippy::mut_from_ref)]
-            fn allocate_from_iter(
-                arena: &Arena,
+            #[track_caller]
+            #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+            fn allocate_from_iter<'b>(
+                arena: &'b $arena_ty,
                 iter: impl ::core::iter::IntoIterator<Item = Self>,
-            ) -> &mut [Self] {
+            ) -> &'b mut [Self] {
                 arena.dropless.alloc_from_iter(iter)
             }
         }
 
         $(
-            impl ArenaAllocatable<$crate::IsNotCopy> for $ty {
+            #[allow(clippy::extra_unused_lifetimes)]
+            impl<$impl_lt> $not_copy_trait for $ty {
                 #[inline]
-                #[allow(clippy::mut_from_ref)]
-                fn allocate_on(self, arena: &Arena) -> &mut Self {
+                #[track_caller]
+                #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+                fn allocate_on<'b>(self, arena: &'b $arena_ty) -> &'b mut Self {
                     if !::core::mem::needs_drop::<Self>() {
                         arena.dropless.alloc(self)
                     } else {
@@ -782,11 +4034,12 @@ macro_rules! declare_arena {
                 }
 
                 #[inline]
-                #[allow(clippy::mut_from_ref)]
-                fn allocate_from_iter(
-                    arena: &Arena,
+                #[track_caller]
+                #[allow(clippy::mut_from_ref, clippy::needless_lifetimes)]
+                fn allocate_from_iter<'b>(
+                    arena: &'b $arena_ty,
                     iter: impl ::core::iter::IntoIterator<Item = Self>,
-                ) -> &mut [Self] {
+                ) -> &'b mut [Self] {
                     if !::core::mem::needs_drop::<Self>() {
                         arena.dropless.alloc_from_iter(iter)
                     } else {
@@ -796,15 +4049,26 @@ macro_rules! declare_arena {
             }
         )*
 
-        impl Arena {
+        // Not every user needs every method, so don't warn about unused ones in non-`pub` arenas.
+        #[allow(dead_code)]
+        impl$(<$lt>)? $arena_ty {
+            /// Clears all sub-arenas, running drop glue on the objects that need it.
+            pub fn clear(&mut self) {
+                self.dropless.clear();
+                $(self.$drop.clear();)?
+                $(self.$name.clear();)*
+            }
+
             #[inline]
+            #[track_caller]
             #[allow(clippy::mut_from_ref)]
-            pub fn alloc<T: ArenaAllocatable<C>, C>(&self, value: T) -> &mut T {
+            pub fn alloc<T: $trait<$($lt,)? C>, C>(&self, value: T) -> &mut T {
                 value.allocate_on(self)
             }
 
             // Any type that impls `Copy` can have slices be arena-allocated in the `DroplessArena`.
             #[inline]
+            #[track_caller]
             #[allow(clippy::mut_from_ref)]
             pub fn alloc_slice<T: ::core::marker::Copy>(&self, value: &[T]) -> &mut [T] {
                 if value.is_empty() {
@@ -813,7 +4077,23 @@ macro_rules! declare_arena {
                 self.dropless.alloc_slice(value)
             }
 
+            // Works for slices of any allocatable type: like in `alloc`, clones of types that
+            // need dropping go to their `TypedArena`, and all others to the `DroplessArena`.
+            #[inline]
+            #[track_caller]
+            #[allow(clippy::mut_from_ref)]
+            pub fn alloc_slice_clone<T: $trait<$($lt,)? C> + ::core::clone::Clone, C>(
+                &self,
+                slice: &[T],
+            ) -> &mut [T] {
+                if slice.is_empty() {
+                    return &mut [];
+                }
+                T::allocate_from_iter(self, slice.iter().cloned())
+            }
+
             #[inline]
+            #[track_caller]
             pub fn alloc_str(&self, string: &str) -> &str {
                 if string.is_empty() {
                     return "";
@@ -821,16 +4101,162 @@ macro_rules! declare_arena {
                 self.dropless.alloc_str(string)
             }
 
+            #[track_caller]
             #[allow(clippy::mut_from_ref)]
-            pub fn alloc_from_iter<T: ArenaAllocatable<C>, C>(
+            pub fn alloc_from_iter<T: $trait<$($lt,)? C>, C>(
                 &self,
                 iter: impl ::core::iter::IntoIterator<Item = T>,
             ) -> &mut [T] {
                 T::allocate_from_iter(self, iter)
             }
+
+            $(
+                #[inline]
+                #[track_caller]
+                #[allow(clippy::mut_from_ref)]
+                pub fn alloc_any<T: 'static>(&self, value: T) -> &mut T {
+                    if !::core::mem::needs_drop::<T>() {
+                        self.dropless.alloc(value)
+                    } else {
+                        self.$drop.alloc(value)
+                    }
+                }
+
+                #[track_caller]
+                #[allow(clippy::mut_from_ref)]
+                pub fn alloc_any_from_iter<T: 'static>(
+                    &self,
+                    iter: impl ::core::iter::IntoIterator<Item = T>,
+                ) -> &mut [T] {
+                    if !::core::mem::needs_drop::<T>() {
+                        self.dropless.alloc_from_iter(iter)
+                    } else {
+                        self.$drop.alloc_from_iter(iter)
+                    }
+                }
+            )?
         }
-    }
+    };
+
+    // Fills in the parts that depend on whether the arena has a lifetime parameter.
+    (@lifetime $attrs:tt ($vis:vis) $arena:ident $trait:ident $fields:tt $drop:tt) => {
+        $crate::declare_arena!(
+            @impl $attrs ($vis) $arena [] '__arena, $arena,
+            $trait, $trait<$crate::IsCopy>, $trait<$crate::IsNotCopy>,
+            $fields $drop
+        );
+    };
+    (@lifetime $attrs:tt ($vis:vis) $arena:ident<$lt:lifetime> $trait:ident $fields:tt $drop:tt) => {
+        $crate::declare_arena!(
+            @impl $attrs ($vis) $arena [$lt] $lt, $arena<$lt>,
+            $trait, $trait<$lt, $crate::IsCopy>, $trait<$lt, $crate::IsNotCopy>,
+            $fields $drop
+        );
+    };
+
+    // Fills in the name of the trait.
+    (@trait $attrs:tt ($vis:vis) $arena:ident $(<$lt:lifetime>)? [] $fields:tt $drop:tt) => {
+        $crate::declare_arena!(
+            @lifetime $attrs ($vis) $arena $(<$lt>)? ArenaAllocatable $fields $drop
+        );
+    };
+    (
+        @trait $attrs:tt ($vis:vis) $arena:ident $(<$lt:lifetime>)? [$trait:ident]
+        $fields:tt $drop:tt
+    ) => {
+        $crate::declare_arena!(@lifetime $attrs ($vis) $arena $(<$lt>)? $trait $fields $drop);
+    };
+
+    // The forms accepted from users.
+    ($(#[$attr:meta])* [$($name:ident: $ty:ty,)*] $(, $drop:ident)?) => {
+        $crate::declare_arena!(
+            @trait [$(#[$attr])*] (pub) Arena [] [$($name: $ty,)*] [$($drop)?]
+        );
+    };
+    ($(#[$attr:meta])* $vis:vis [$($name:ident: $ty:ty,)*] $(, $drop:ident)?) => {
+        $crate::declare_arena!(
+            @trait [$(#[$attr])*] ($vis) Arena [] [$($name: $ty,)*] [$($drop)?]
+        );
+    };
+    (
+        $(#[$attr:meta])* $arena:ident $(<$lt:lifetime>)? $(: $trait:ident)?
+        [$($name:ident: $ty:ty,)*] $(, $drop:ident)?
+    ) => {
+        $crate::declare_arena!(
+            @trait [$(#[$attr])*] (pub) $arena $(<$lt>)? [$($trait)?] [$($name: $ty,)*]
+            [$($drop)?]
+        );
+    };
+    (
+        $(#[$attr:meta])* $vis:vis $arena:ident $(<$lt:lifetime>)? $(: $trait:ident)?
+        [$($name:ident: $ty:ty,)*] $(, $drop:ident)?
+    ) => {
+        $crate::declare_arena!(
+            @trait [$(#[$attr])*] ($vis) $arena $(<$lt>)? [$($trait)?] [$($name: $ty,)*]
+            [$($drop)?]
+        );
+    };
+}
+
+/// Declares an arena that is local to the current thread, for scratch allocations without
+/// passing an arena around.
+///
+/// `thread_local_arena!(static SCRATCH: DroplessArena;)` declares a `std::thread_local!` static
+/// `SCRATCH` that holds a `DroplessArena`, to be used as in `SCRATCH.with(|arena| ...)`. The
+/// arena is created on first use and dropped when the thread exits. Any arena type that
+/// implements `Default` can be used, including those from `declare_arena!`. Since the arenas use
+/// interior mutability, no `RefCell` is needed.
+///
+/// A `macro_rules!` macro can't derive the name `with_scratch` from `SCRATCH`, so an accessor
+/// function is only declared if it is named: `static SCRATCH: DroplessArena, fn with_scratch;`
+/// also declares `fn with_scratch<R>(f: impl FnOnce(&DroplessArena) -> R) -> R`, which calls `f`
+/// with the calling thread's arena. `fn with_scratch: DroplessArena;` declares just the function,
+/// with the static hidden inside it.
+///
+/// ```rust
+/// use stable_arena::{thread_local_arena, DroplessArena};
+///
+/// thread_local_arena!(static SCRATCH: DroplessArena, fn with_scratch;);
+///
+/// let len = with_scratch(|arena| arena.alloc_str("hello").len());
+/// assert_eq!(len, 5);
+/// assert_eq!(SCRATCH.with(|arena| arena.memory_used()), 8);
+/// ```
+///
+/// References into the arena can't escape the closure, but the memory is only reclaimed when the
+/// thread exits, so this suits allocations whose total size is bounded. A visibility and
+/// attributes, including doc comments, can be put before the `static` or `fn`. They apply to
+/// both the static and the accessor.
+///
+/// This macro uses `std::thread_local!`, so it requires the `std` crate.
+#[macro_export]
+macro_rules! thread_local_arena {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty, fn $accessor:ident $(;)?) => {
+        $crate::thread_local_arena!($(#[$attr])* $vis static $name: $ty;);
+        $(#[$attr])*
+        $vis fn $accessor<R>(f: impl ::core::ops::FnOnce(&$ty) -> R) -> R {
+            $name.with(f)
+        }
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty $(;)?) => {
+        ::std::thread_local! {
+            $(#[$attr])*
+            $vis static $name: $ty = <$ty as ::core::default::Default>::default();
+        }
+    };
+    ($(#[$attr:meta])* $vis:vis fn $name:ident: $ty:ty $(;)?) => {
+        $(#[$attr])*
+        $vis fn $name<R>(f: impl ::core::ops::FnOnce(&$ty) -> R) -> R {
+            ::std::thread_local! {
+                static ARENA: $ty = <$ty as ::core::default::Default>::default();
+            }
+            ARENA.with(f)
+        }
+    };
 }
 
+#[cfg(feature = "bumpalo-compat")]
+pub mod compat;
+
 #[cfg(test)]
 mod tests;
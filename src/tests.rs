@@ -1,39 +1,136 @@
 // The crate is `no_std`, but the test harness links `std`, so pull in the items the tests need
 // (including the `vec!` and `thread_local!` macros) that the `core` prelude doesn't provide.
+use std::alloc::{GlobalAlloc, Layout, System};
 #[cfg(feature = "from-iter")]
 use std::boxed::Box;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::marker::PhantomPinned;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+#[cfg(feature = "from-iter")]
+use std::rc::Rc;
 use std::string::{String, ToString};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::vec::Vec;
-use std::{thread_local, vec};
+use std::{format, thread_local, vec};
+
+#[cfg(feature = "from-iter")]
+use super::CapExceeded;
+use super::{
+    AllocError, ArenaConfig, ArenaPool, ArenaStats, ByteBuf, ClearPolicy, DropArena, DroplessArena,
+    DroplessArenaPool, FrozenArena, Idx, IndexedArena, PoolLock, SliceInterner, StrInterner,
+    SyncDroplessArena, TypedArena,
+};
+
+/// Fails to compile if `T` is `Sync`: then both impls apply and the `_` can't be inferred.
+macro_rules! assert_not_sync {
+    ($($ty:ty),* $(,)?) => {
+        const _: () = {
+            trait AmbiguousIfSync<A> {
+                fn some_item() {}
+            }
+            impl<T: ?Sized> AmbiguousIfSync<()> for T {}
+            impl<T: ?Sized + Sync> AmbiguousIfSync<u8> for T {}
+            $(let _ = <$ty as AmbiguousIfSync<_>>::some_item;)*
+        };
+    };
+}
+
+// See the comment at the top of the crate for why these must not be `Sync`.
+assert_not_sync!(
+    TypedArena<i32>,
+    DroplessArena,
+    DropArena<'static>,
+    IndexedArena<i32>,
+    StrInterner,
+    SliceInterner<i32>,
+);
+
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn test_sync_impls() {
+    assert_sync::<SyncDroplessArena>();
+    assert_sync::<ArenaPool<i32>>();
+    assert_sync::<DroplessArenaPool>();
+    assert_sync::<FrozenArena<i32>>();
+}
+
+/// A global allocator that counts the allocations made on each thread, so tests can check
+/// that some code path doesn't allocate. It can also be told to fail allocations.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static FAIL_ALLOCATIONS: Cell<bool> = const { Cell::new(false) };
+}
 
-use super::{DroplessArena, TypedArena};
+#[cfg(feature = "zeroize")]
+thread_local! {
+    /// A pattern to look for in memory that is deallocated, and the number of times it was found.
+    static SCAN_FREED: Cell<Option<u64>> = const { Cell::new(None) };
+    static FOUND_IN_FREED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|c| c.set(c.get() + 1));
+        if FAIL_ALLOCATIONS.with(|fail| fail.get()) {
+            return ptr::null_mut();
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "zeroize")]
+        if let Some(pattern) = SCAN_FREED.with(|scan| scan.get()) {
+            let found = (0..layout.size().saturating_sub(7))
+                .filter(|&i| unsafe { ptr.add(i).cast::<u64>().read_unaligned() } == pattern)
+                .count();
+            FOUND_IN_FREED.with(|c| c.set(c.get() + found));
+        }
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Runs `f` with all allocations on the current thread failing.
+fn with_failing_allocations<R>(f: impl FnOnce() -> R) -> R {
+    FAIL_ALLOCATIONS.with(|fail| fail.set(true));
+    let result = f();
+    FAIL_ALLOCATIONS.with(|fail| fail.set(false));
+    result
+}
+
+/// Returns how often `pattern` occurred in the memory that `f` deallocated on the current thread.
+#[cfg(feature = "zeroize")]
+fn count_freed<R>(pattern: u64, f: impl FnOnce() -> R) -> (R, usize) {
+    FOUND_IN_FREED.with(|c| c.set(0));
+    SCAN_FREED.with(|scan| scan.set(Some(pattern)));
+    let result = f();
+    SCAN_FREED.with(|scan| scan.set(None));
+    (result, FOUND_IN_FREED.with(|c| c.get()))
+}
+
+/// Returns the number of allocations that `f` made on the current thread.
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(|c| c.get());
+    let result = f();
+    (result, ALLOCATIONS.with(|c| c.get()) - before)
+}
 
 #[allow(dead_code)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
 struct Point {
     x: i32,
     y: i32,
     z: i32,
 }
 
-impl<T> TypedArena<T> {
-    /// Clears the arena. Deallocates all but the longest chunk which may be reused.
-    fn clear(&mut self) {
-        unsafe {
-            // Clear the last chunk, which is partially filled.
-            let mut chunks_borrow = self.chunks.borrow_mut();
-            if let Some(last_chunk) = chunks_borrow.last_mut() {
-                self.clear_last_chunk(last_chunk);
-                let len = chunks_borrow.len();
-                for mut chunk in chunks_borrow.drain(..len - 1) {
-                    chunk.destroy(chunk.entries);
-                }
-            }
-        }
-    }
-}
-
 #[test]
 fn test_unused() {
     let arena: TypedArena<Point> = TypedArena::default();
@@ -47,278 +144,2849 @@ fn test_unused_dropless() {
 }
 
 #[test]
-fn test_arena_alloc_nested() {
-    struct Inner {
-        value: u8,
+fn test_with_capacity() {
+    let arena: TypedArena<Point> = TypedArena::with_capacity(10_000);
+    assert_eq!(arena.chunks.borrow().len(), 1);
+    for _ in 0..10_000 {
+        arena.alloc(Point { x: 1, y: 2, z: 3 });
     }
-    struct Outer<'a> {
-        inner: &'a Inner,
+    assert_eq!(arena.chunks.borrow().len(), 1);
+}
+
+#[test]
+fn test_dropless_with_capacity() {
+    let arena = DroplessArena::with_capacity(128 * 1024);
+    let big = "a".repeat(100 * 1024);
+    let string = arena.alloc_str(&big);
+    assert_eq!(string, big);
+    assert_eq!(arena.chunks.borrow().len(), 1);
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_alloc_from_iter_reuses_chunk() {
+    // Both the exact-size path and the collecting path must fill the tail of the current chunk
+    // before starting a new one.
+    let arena: TypedArena<u64> = TypedArena::with_capacity(100);
+    let capacity = arena.capacity();
+    while arena.len() + 7 <= capacity {
+        arena.alloc(0);
+        arena.alloc_from_iter([1, 2, 3]);
+        arena.alloc_from_iter((0..6).filter(|x| x % 2 == 0));
+        assert_eq!(arena.chunks.borrow().len(), 1);
     }
-    enum EI<'e> {
-        I(Inner),
-        O(Outer<'e>),
+    arena.alloc_from_iter(0..7);
+    assert_eq!(arena.chunks.borrow().len(), 2);
+
+    let arena = DroplessArena::with_capacity(800);
+    let capacity = arena.memory_reserved();
+    while arena.memory_used() + 7 * size_of::<u64>() <= capacity {
+        arena.alloc(0u64);
+        arena.alloc_from_iter([1u64, 2, 3]);
+        arena.alloc_from_iter((0..6u64).filter(|x| x % 2 == 0));
+        assert_eq!(arena.chunks.borrow().len(), 1);
     }
+    arena.alloc_from_iter(0..7u64);
+    assert_eq!(arena.chunks.borrow().len(), 2);
+}
 
-    struct Wrap(DroplessArena);
+#[test]
+fn test_typed_arena_iter() {
+    let mut arena: TypedArena<Point> = TypedArena::default();
+    assert_eq!(arena.iter().count(), 0);
+    // Enough to span several chunks.
+    for i in 0..1000 {
+        arena.alloc(Point { x: i, y: 0, z: 0 });
+    }
+    assert!(arena.chunks.borrow().len() > 1);
+    assert!(arena.iter().map(|p| p.x).eq(0..1000));
 
-    impl Wrap {
-        fn alloc_inner<F: Fn() -> Inner>(&self, f: F) -> &Inner {
-            match self.0.alloc(EI::I(f())) {
-                EI::I(i) => i,
-                _ => panic!("mismatch"),
-            }
+    for p in arena.iter_mut() {
+        p.y = p.x * 2;
+    }
+    assert!(arena.iter().map(|p| p.y).eq((0..1000).map(|i| i * 2)));
+
+    arena.clear();
+    assert_eq!(arena.iter().count(), 0);
+    arena.alloc(Point { x: 7, y: 8, z: 9 });
+    assert_eq!(
+        arena.iter().collect::<Vec<_>>(),
+        [&Point { x: 7, y: 8, z: 9 }]
+    );
+}
+
+#[test]
+fn test_typed_arena_try_alloc() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    let p = arena.try_alloc(Point { x: 1, y: 2, z: 3 }).unwrap();
+    assert_eq!(p, &Point { x: 1, y: 2, z: 3 });
+}
+
+#[test]
+fn test_typed_arena_try_grow_failure() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    // The layout computation overflows.
+    assert!(arena.try_grow(usize::MAX).is_none());
+    // The arena is still usable afterwards.
+    assert_eq!(arena.alloc(Point { x: 1, y: 2, z: 3 }).x, 1);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_typed_arena_try_grow_oom() {
+    let mut arena: TypedArena<Point> = TypedArena::default();
+    arena.alloc(Point { x: 1, y: 2, z: 3 });
+    // A valid layout that no allocator can satisfy.
+    assert!(
+        arena
+            .try_grow(isize::MAX as usize / 2 / size_of::<Point>())
+            .is_none()
+    );
+    assert_eq!(arena.iter().count(), 1);
+}
+
+#[test]
+fn test_typed_arena_alloc_from_iter_exact() {
+    let arena: TypedArena<Point> = TypedArena::with_capacity(1000);
+    let (slice, allocations) = count_allocations(|| {
+        arena.alloc_from_iter_exact((0..1000).map(|x| Point { x, y: 0, z: 0 }))
+    });
+    assert_eq!(allocations, 0);
+    assert!(slice.iter().map(|p| p.x).eq(0..1000));
+    assert!(arena.alloc_from_iter_exact([]).is_empty());
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_dropless_try_reserve() {
+    let arena = DroplessArena::default();
+    assert_eq!(arena.try_reserve(0), Ok(()));
+    assert_eq!(
+        with_failing_allocations(|| arena.try_reserve(1)),
+        Err(AllocError)
+    );
+    assert_eq!(arena.stats().chunk_count, 0);
+    assert_eq!(arena.try_reserve(1000), Ok(()));
+    let reserved = arena.stats().bytes_reserved;
+    assert!(reserved >= 1000);
+    // The current chunk has space left, so nothing is allocated.
+    let (result, allocations) = count_allocations(|| arena.try_reserve(100));
+    assert_eq!((result, allocations), (Ok(()), 0));
+
+    assert_eq!(
+        with_failing_allocations(|| arena.try_reserve(1 << 20)),
+        Err(AllocError)
+    );
+    assert_eq!(arena.try_reserve(usize::MAX), Err(AllocError));
+    assert_eq!(arena.stats().bytes_reserved, reserved);
+
+    assert_eq!(arena.try_reserve(1 << 20), Ok(()));
+    let before = arena.stats().chunk_count;
+    arena.alloc_slice_fill_copy(1 << 20, 0u8);
+    assert_eq!(arena.stats().chunk_count, before);
+}
+
+#[test]
+fn test_dropless_checkpoint() {
+    let arena = DroplessArena::default();
+    let empty = arena.checkpoint();
+    let first: *const u64 = arena.alloc(1u64);
+    let checkpoint = arena.checkpoint();
+    let used = arena.memory_used();
+
+    // Within a chunk.
+    let second: *const u64 = arena.alloc(2u64);
+    arena.alloc_str("hello");
+    // SAFETY: The allocations since the checkpoint aren't used anymore.
+    unsafe { arena.rewind(checkpoint) };
+    assert_eq!(arena.memory_used(), used);
+    assert_eq!(arena.alloc(3u64) as *const u64, second);
+
+    // Across chunks.
+    // SAFETY: As above.
+    unsafe { arena.rewind(checkpoint) };
+    for i in 0..10_000u64 {
+        arena.alloc(i);
+    }
+    assert!(arena.stats().chunk_count > 1);
+    // SAFETY: As above.
+    unsafe { arena.rewind(checkpoint) };
+    assert_eq!(arena.stats().chunk_count, 1);
+    assert_eq!(arena.memory_used(), used);
+    assert_eq!(arena.alloc(4u64) as *const u64, second);
+    // SAFETY: `first` is the only reference that is still used afterwards.
+    assert_eq!(unsafe { *first }, 1);
+
+    // SAFETY: Nothing allocated in the arena is used anymore.
+    unsafe { arena.rewind(empty) };
+    assert_eq!(arena.stats().chunk_count, 0);
+    assert_eq!(*arena.alloc(5u64), 5);
+}
+
+#[test]
+fn test_dropless_alloc_slice_from_vec() {
+    let arena = DroplessArena::default();
+    let vec: Vec<u32> = (0..100).map(|i| i * i).collect();
+    let slice = arena.alloc_slice_from_vec(vec);
+    assert_eq!(slice.len(), 100);
+    assert_eq!(slice[9], 81);
+    assert!(arena.contains_ptr(slice.as_ptr()));
+    assert!(arena.alloc_slice_from_vec(Vec::<u32>::new()).is_empty());
+}
+
+#[test]
+fn test_dropless_alloc_raw_slice() {
+    let arena = DroplessArena::default();
+    let data = [1u32, 2, 3, 4, 5];
+    let (ptr, len) = arena.alloc_raw_slice(&data);
+    assert_eq!(len, data.len());
+    assert!(arena.contains_ptr(ptr.as_ptr()));
+    assert_eq!(ptr.as_ptr().addr() % align_of::<u32>(), 0);
+    arena.alloc_slice(&[6u32; 100]);
+    // SAFETY: The arena is still alive and hasn't been cleared.
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(ptr.as_ptr(), len) },
+        &data
+    );
+}
+
+#[cfg(not(miri))] // Too slow under miri.
+#[test]
+fn test_dropless_alloc_many_small() {
+    let arena = DroplessArena::default();
+    let mut last: Option<&u32> = None;
+    for i in 0..1_000_000u32 {
+        let x = arena.alloc(i);
+        assert!(ptr::from_ref(x).is_aligned());
+        if let Some(last) = last {
+            assert_eq!(*last, i - 1);
         }
-        fn alloc_outer<'a, F: Fn() -> Outer<'a>>(&'a self, f: F) -> &'a Outer<'a> {
-            match self.0.alloc(EI::O(f())) {
-                EI::O(o) => o,
-                _ => panic!("mismatch"),
-            }
+        last = Some(x);
+    }
+    // Each allocation is rounded up to the alignment of `usize`.
+    assert_eq!(arena.memory_used(), 1_000_000 * align_of::<usize>());
+
+    // Over-aligned allocations still take the slow path.
+    #[derive(Clone, Copy)]
+    #[repr(align(64))]
+    struct Aligned(u8);
+    let aligned = arena.alloc(Aligned(1));
+    assert!(ptr::from_ref(aligned).is_aligned());
+    assert_eq!(aligned.0, 1);
+}
+
+#[test]
+fn test_dropless_alloc_slice_over_aligned() {
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    #[repr(align(64))]
+    struct Cacheline([u8; 64]);
+
+    let arena = DroplessArena::default();
+    for len in 1..100 {
+        // Leave `end` at an address that is only aligned to `DROPLESS_ALIGNMENT`.
+        arena.alloc(len as u8);
+        let lines = vec![Cacheline([len as u8; 64]); len];
+        let slice = arena.alloc_slice(&lines);
+        assert_eq!(slice.as_ptr().addr() % 64, 0);
+        assert!(
+            slice
+                .iter()
+                .all(|line| ptr::from_ref(line).addr() % 64 == 0)
+        );
+        assert_eq!(slice, &lines[..]);
+    }
+    assert!(arena.chunks.borrow().len() > 1);
+}
+
+#[test]
+fn test_dropless_alloc_slice_aligned() {
+    let arena = DroplessArena::default();
+    for len in 1..20 {
+        arena.alloc(1u8);
+        let floats: Vec<f32> = (0..len).map(|i| i as f32).collect();
+        let slice = arena.alloc_slice_aligned(&floats, 32);
+        assert_eq!(slice, floats.as_slice());
+        assert_eq!(slice.as_ptr().addr() % 32, 0);
+    }
+    let slice = arena.alloc_slice_aligned(&[1u64, 2], 4096);
+    assert_eq!(slice.as_ptr().addr() % 4096, 0);
+}
+
+#[test]
+#[should_panic]
+fn test_dropless_alloc_slice_aligned_too_small() {
+    DroplessArena::default().alloc_slice_aligned(&[1u64], 4);
+}
+
+#[test]
+fn test_byte_buf() {
+    let arena = DroplessArena::default();
+    let parts: [&[u8]; 4] = [b"hello", b", ", b"", b"world"];
+    let mut buf = ByteBuf::new(&arena);
+    assert!(buf.is_empty());
+    for part in parts {
+        buf.push_bytes(part);
+    }
+    assert_eq!(buf.len(), 12);
+    let bytes = buf.finish();
+    assert_eq!(bytes, parts.concat());
+    // The buffer grew in place and gave back the space it didn't use.
+    assert_eq!(arena.memory_used(), 16);
+    assert!(arena.contains_ptr(bytes.as_ptr()));
+
+    // Growing beyond the current chunk, with other allocations in between.
+    let mut buf = ByteBuf::new(&arena);
+    let mut expected = Vec::new();
+    for i in 0..1000u32 {
+        let part = i.to_string();
+        buf.push_bytes(part.as_bytes());
+        expected.extend_from_slice(part.as_bytes());
+        if i % 100 == 0 {
+            arena.alloc(i);
         }
     }
+    let bytes = buf.finish();
+    assert_eq!(bytes, expected);
+    assert!(arena.stats().chunk_count > 1);
+    assert_eq!(ByteBuf::new(&arena).finish(), b"");
+}
 
-    let arena = Wrap(DroplessArena::default());
+#[test]
+fn test_byte_buf_after_reserve() {
+    let arena = DroplessArena::default();
+    arena.alloc(0u8);
+    let free = arena.memory_reserved() - arena.memory_used();
+    arena.alloc_uninit_slice::<u8>(free - 64);
+    // The buffer's first allocation takes the rest of the chunk.
+    let mut buf = ByteBuf::new(&arena);
+    buf.push_bytes(&[1; 64]);
+    assert_eq!(arena.memory_used(), arena.memory_reserved());
+    // The new chunk may end right where the buffer starts, but the buffer must not grow into it.
+    arena.try_reserve(4096).unwrap();
+    buf.push_bytes(&[2; 100]);
+    let bytes = buf.finish();
+    assert_eq!(bytes, [[1; 64].as_slice(), &[2; 100]].concat());
+    let (last_start, last_len) = arena.iter_chunks().last().unwrap();
+    let last_chunk = last_start.addr()..last_start.addr() + last_len;
+    assert!(last_chunk.contains(&bytes.as_ptr().addr()));
+    assert!(last_chunk.contains(&(bytes.as_ptr().addr() + bytes.len() - 1)));
+}
 
-    let result = arena.alloc_outer(|| Outer {
-        inner: arena.alloc_inner(|| Inner { value: 10 }),
+#[test]
+fn test_dropless_alloc_fmt() {
+    use core::fmt::Write;
+
+    let arena = DroplessArena::default();
+    let x = 42;
+    assert_eq!(arena.alloc_fmt(format_args!("x = {x}")), "x = 42");
+    assert_eq!(arena.alloc_fmt(format_args!("")), "");
+    // Longer than a chunk, so the string has to be moved while it's built.
+    let numbers: Vec<u32> = (0..10_000).collect();
+    let formatted = arena.alloc_fmt(format_args!("{numbers:?}"));
+    assert_eq!(formatted, format!("{numbers:?}"));
+    assert!(arena.contains_ptr(formatted.as_ptr()));
+
+    let mut buf = ByteBuf::new(&arena);
+    write!(buf, "{}:{}", x, 7).unwrap();
+    buf.write_char('!').unwrap();
+    assert_eq!(buf.finish(), b"42:7!");
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_dropless_alloc_fmt_failing_impl() {
+    struct Failing;
+    impl core::fmt::Display for Failing {
+        fn fmt(&self, _: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            Err(core::fmt::Error)
+        }
+    }
+
+    let arena = DroplessArena::default();
+    let message = panic_message(|| {
+        arena.alloc_fmt(format_args!("{Failing}"));
     });
+    assert!(message.contains("formatting trait implementation returned an error"));
+}
 
-    assert_eq!(result.inner.value, 10);
+#[test]
+fn test_dropless_alloc_concat() {
+    let arena = DroplessArena::default();
+    let slice = arena.alloc_concat(&[&[1, 2], &[], &[3, 4, 5]]);
+    assert_eq!(slice, &[1, 2, 3, 4, 5]);
+    assert!(arena.alloc_concat::<u8>(&[&[], &[]]).is_empty());
+    assert!(arena.alloc_concat::<u8>(&[]).is_empty());
+    assert_eq!(
+        arena.alloc_concat(&[b"arena".as_slice(), b"-", b"backed"]),
+        b"arena-backed"
+    );
 }
 
 #[test]
-fn test_copy() {
+fn test_alloc_array() {
+    let arena = DroplessArena::default();
+    let bytes: &mut [u8; 32] = arena.alloc_array([7; 32]);
+    bytes[31] = 8;
+    assert_eq!(bytes[..31], [7; 31]);
+    assert_eq!(bytes[31], 8);
+    assert_eq!(arena.alloc_array::<u8, 0>([]), &[]);
+
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<(String, DropCounter<'_>)> = TypedArena::default();
+    let strings = arena.alloc_array(
+        ["a", "b", "c", "d"].map(|s| (s.to_string(), DropCounter { count: &counter })),
+    );
+    assert_eq!(strings[3].0, "d");
+    assert_eq!(arena.len(), 4);
+    arena.clear();
+    assert_eq!(counter.get(), 4);
+}
+
+#[test]
+fn test_typed_arena_reset() {
+    let counter = Cell::new(0);
+    let mut arena = TypedArena::default();
+    for round in 1..=3 {
+        let (_, allocations) = count_allocations(|| {
+            for _ in 0..2000 {
+                arena.alloc(DropCounter { count: &counter });
+            }
+        });
+        if round == 1 {
+            assert!(arena.chunks.borrow().len() >= 3);
+        } else {
+            // The chunks of the first round are reused.
+            assert_eq!(allocations, 0);
+        }
+        let reserved = arena.memory_reserved();
+        let chunk_count = arena.stats().chunk_count;
+        arena.reset();
+        assert_eq!(counter.get(), round * 2000);
+        assert_eq!(arena.len(), 0);
+        assert_eq!(arena.memory_reserved(), reserved);
+        assert_eq!(arena.stats().chunk_count, chunk_count);
+    }
+
+    // A slice that is too large for the spare chunks gets a new one, and the spare chunk that is
+    // too small is freed, so that the chunks keep growing.
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    arena.alloc(1);
+    arena.reset();
+    assert_eq!(arena.alloc_slice(10_000, |i| i as u64).len(), 10_000);
+    assert_eq!(arena.stats().chunk_count, 1);
+    assert_eq!(*arena.alloc(2), 2);
+    assert_eq!(arena.len(), 10_001);
+
+    // `clear` keeps the longest chunk, also if it is a spare chunk.
+    let longest = arena
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len() * size_of::<u64>())
+        .max()
+        .unwrap();
+    arena.reset();
+    arena.clear();
+    assert_eq!(arena.stats().chunk_count, 1);
+    assert_eq!(arena.memory_reserved(), longest);
+    assert_eq!(*arena.alloc(3), 3);
+
+    // Of several spare chunks, the first one that fits is taken.
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    for i in 0..5000 {
+        arena.alloc(i);
+    }
+    let sizes: Vec<usize> = arena
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len())
+        .collect();
+    assert!(sizes.len() >= 3);
+    arena.reset();
+    arena.alloc(0);
+    arena.alloc_slice(sizes[1] + 1, |i| i as u64);
+    let new_sizes: Vec<usize> = arena
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len())
+        .collect();
+    assert_eq!(new_sizes, [sizes[0], sizes[2]]);
+    assert_eq!(arena.spare_chunks.borrow().len(), sizes.len() - 3);
+}
+
+#[test]
+fn test_typed_arena_drop_continues_after_panic() {
+    struct PanicOnDrop<'a> {
+        panics: bool,
+        _counter: DropCounter<'a>,
+    }
+
+    impl Drop for PanicOnDrop<'_> {
+        fn drop(&mut self) {
+            if self.panics {
+                panic!("panic in drop");
+            }
+        }
+    }
+
+    let counter = Cell::new(0);
     let arena = TypedArena::default();
-    #[cfg(not(miri))]
-    const N: usize = 100000;
-    #[cfg(miri)]
-    const N: usize = 1000;
-    for _ in 0..N {
-        arena.alloc(Point { x: 1, y: 2, z: 3 });
+    for i in 0..10 {
+        arena.alloc(PanicOnDrop {
+            panics: i == 5,
+            _counter: DropCounter { count: &counter },
+        });
     }
+    let result = panic::catch_unwind(AssertUnwindSafe(|| drop(arena)));
+    assert!(result.is_err());
+    // The objects on both sides of the panicking one are dropped.
+    assert_eq!(counter.get(), 10);
 }
 
-// #[bench]
-// fn bench_copy(b: &mut Bencher) {
-//     let arena = TypedArena::default();
-//     b.iter(|| arena.alloc(Point { x: 1, y: 2, z: 3 }))
-// }
-//
-// #[bench]
-// fn bench_copy_nonarena(b: &mut Bencher) {
-//     b.iter(|| {
-//         let _: Box<_> = Box::new(Point { x: 1, y: 2, z: 3 });
-//     })
-// }
+#[test]
+fn test_typed_arena_drain() {
+    let counter = Cell::new(0);
+    let mut arena = TypedArena::default();
+    for i in 0..2000 {
+        arena.alloc((i, DropCounter { count: &counter }));
+    }
+    let reserved = arena.memory_reserved();
+    let mut drain = arena.drain();
+    assert_eq!(drain.len(), 2000);
+    for i in 0..1000 {
+        assert_eq!(drain.next().unwrap().0, i);
+    }
+    assert_eq!(drain.len(), 1000);
+    assert_eq!(counter.get(), 1000);
+    drop(drain);
+    assert_eq!(counter.get(), 2000);
+    assert!(arena.is_empty());
+    assert_eq!(arena.memory_reserved(), reserved);
 
-#[allow(dead_code)]
-struct Noncopy {
-    string: String,
-    array: Vec<i32>,
+    arena.alloc((0, DropCounter { count: &counter }));
+    let values: Vec<i32> = arena.drain().map(|(i, _)| i).collect();
+    assert_eq!(values, [0]);
+    assert_eq!(counter.get(), 2001);
+    assert_eq!(arena.drain().count(), 0);
+
+    // Leaking the iterator leaks the objects.
+    arena.alloc((0, DropCounter { count: &counter }));
+    std::mem::forget(arena.drain());
+    drop(arena);
+    assert_eq!(counter.get(), 2001);
 }
 
 #[test]
-fn test_noncopy() {
-    let arena = TypedArena::default();
-    #[cfg(not(miri))]
-    const N: usize = 100000;
-    #[cfg(miri)]
-    const N: usize = 1000;
-    for _ in 0..N {
-        arena.alloc(Noncopy {
-            string: "hello world".to_string(),
-            array: vec![1, 2, 3, 4, 5],
+fn test_typed_arena_clear_and_collect() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<(Point, DropCounter<'_>)> = TypedArena::default();
+    for x in 0..1000 {
+        arena.alloc((Point { x, y: 0, z: 0 }, DropCounter { count: &counter }));
+    }
+    let chunk_count = arena.chunks.borrow().len();
+    assert!(chunk_count > 1);
+
+    let even = arena.clear_and_collect(|(point, _)| point.x % 2 == 0);
+    assert!(
+        even.iter()
+            .map(|(point, _)| point.x)
+            .eq((0..1000).step_by(2))
+    );
+    // The odd ones were dropped, the even ones moved out.
+    assert_eq!(counter.get(), 500);
+    assert!(arena.is_empty());
+    assert_eq!(arena.chunks.borrow().len(), 1);
+
+    arena.alloc((Point { x: 1, y: 0, z: 0 }, DropCounter { count: &counter }));
+    drop(arena);
+    assert_eq!(counter.get(), 501);
+    drop(even);
+    assert_eq!(counter.get(), 1001);
+}
+
+#[test]
+fn test_typed_arena_from_iter() {
+    let mut arena = TypedArena::from_iter(0..100u64);
+    assert_eq!(arena.len(), 100);
+    assert_eq!(arena.iter().sum::<u64>(), 4950);
+
+    // Without a useful size hint, the elements end up in several chunks.
+    let mut arena: TypedArena<u64> = (0..10_000).filter(|_| true).collect();
+    assert!(arena.chunks.borrow().len() > 1);
+    assert_eq!(arena.len(), 10_000);
+    assert!(arena.iter().copied().eq(0..10_000));
+    arena.alloc(1);
+    assert_eq!(arena.len(), 10_001);
+    arena.clear();
+    assert!(arena.is_empty());
+}
+
+#[test]
+fn test_typed_arena_alloc_slice() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<(String, DropCounter<'_>)> = TypedArena::default();
+    let slice = arena.alloc_slice(100, |i| (i.to_string(), DropCounter { count: &counter }));
+    assert_eq!(slice.len(), 100);
+    assert_eq!(slice[42].0, "42");
+    assert!(arena.alloc_slice(0, |_| unreachable!()).is_empty());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_slice(10, |i| {
+            if i == 3 {
+                panic!("boom");
+            }
+            (i.to_string(), DropCounter { count: &counter })
         });
+    }));
+    assert!(result.is_err());
+    // The three elements constructed before the panic were dropped right away.
+    assert_eq!(counter.get(), 3);
+    assert_eq!(arena.iter().count(), 100);
+
+    arena.clear();
+    assert_eq!(counter.get(), 103);
+}
+
+#[test]
+fn test_typed_arena_alloc_from_iter_exact_panic() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<DropCounter<'_>> = TypedArena::default();
+    arena.alloc(DropCounter { count: &counter });
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_from_iter_exact((0..10).map(|i| {
+            if i == 4 {
+                panic!("boom");
+            }
+            DropCounter { count: &counter }
+        }));
+    }));
+    assert!(result.is_err());
+    // The four elements written before the panic were dropped, and their storage given back.
+    assert_eq!(counter.get(), 4);
+    assert_eq!(arena.iter().count(), 1);
+    drop(arena);
+    assert_eq!(counter.get(), 5);
+}
+
+#[test]
+fn test_typed_arena_slice_builder() {
+    let arena: TypedArena<String> = TypedArena::default();
+    let mut builder = arena.slice_builder();
+    assert!(builder.is_empty());
+    for i in 0..100 {
+        builder.push(i.to_string());
+        // Other objects may be allocated while the slice is being built.
+        if i % 10 == 0 {
+            arena.alloc(String::from("other"));
+        }
+    }
+    assert_eq!(builder.len(), 100);
+    let strings = builder.finish();
+    assert!(strings.iter().map(|s| s.parse::<i32>().unwrap()).eq(0..100));
+    // The builder can be reused.
+    assert!(builder.is_empty());
+    builder.extend([String::from("a"), String::from("b")]);
+    assert_eq!(builder.finish(), ["a", "b"]);
+    assert!(builder.finish().is_empty());
+    assert!(strings.iter().map(|s| s.parse::<i32>().unwrap()).eq(0..100));
+
+    let counter = Cell::new(0);
+    {
+        let arena = TypedArena::default();
+        let mut builder = arena.slice_builder();
+        for _ in 0..100 {
+            builder.push(DropCounter { count: &counter });
+        }
+        // Dropping the builder without finishing drops the collected objects.
+        drop(builder);
+        assert_eq!(counter.get(), 100);
+        let mut builder = arena.slice_builder();
+        builder.push(DropCounter { count: &counter });
+        assert_eq!(builder.finish().len(), 1);
+        drop(builder);
+        assert_eq!(counter.get(), 100);
+    }
+    assert_eq!(counter.get(), 101);
+}
+
+#[test]
+fn test_typed_arena_alloc_from_slice() {
+    let points: Vec<Point> = (0..1000).map(|x| Point { x, y: 1, z: 2 }).collect();
+    let mut arena: TypedArena<Point> = TypedArena::default();
+    arena.alloc(Point { x: -1, y: 0, z: 0 });
+    let slice = arena.alloc_from_slice(&points);
+    assert_eq!(slice, &points[..]);
+    slice[0].y = 5;
+    assert!(arena.alloc_from_slice(&[]).is_empty());
+    assert_eq!(arena.iter().count(), 1001);
+    arena.clear();
+    assert_eq!(arena.iter().count(), 0);
+}
+
+#[test]
+fn test_stats() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    assert_eq!(arena.stats(), ArenaStats::default());
+    for x in 0..1000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+    }
+    let stats = arena.stats();
+    assert!(stats.chunk_count >= 2);
+    assert_eq!(stats.bytes_used, 1000 * size_of::<Point>());
+    assert!(stats.bytes_reserved >= stats.bytes_used);
+    assert_eq!(stats.bytes_wasted, stats.bytes_reserved - stats.bytes_used);
+
+    let arena = DroplessArena::default();
+    assert_eq!(arena.stats(), ArenaStats::default());
+    for x in 0..1000u64 {
+        arena.alloc(x);
+    }
+    let stats = arena.stats();
+    assert!(stats.chunk_count >= 2);
+    assert_eq!(stats.bytes_used, 1000 * size_of::<u64>());
+    assert!(stats.bytes_reserved >= stats.bytes_used);
+    assert_eq!(stats.bytes_wasted, stats.bytes_reserved - stats.bytes_used);
+}
+
+#[test]
+fn test_memory_used_and_reserved() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    assert_eq!((arena.memory_used(), arena.memory_reserved()), (0, 0));
+    for x in 0..1000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+    }
+    assert_eq!(arena.memory_used(), 1000 * size_of::<Point>());
+    assert_eq!(arena.memory_reserved(), arena.stats().bytes_reserved);
+
+    let arena = DroplessArena::default();
+    assert_eq!((arena.memory_used(), arena.memory_reserved()), (0, 0));
+    for x in 0..1000u64 {
+        arena.alloc(x);
+    }
+    assert_eq!(arena.memory_used(), 1000 * size_of::<u64>());
+    assert_eq!(arena.memory_reserved(), arena.stats().bytes_reserved);
+}
+
+#[test]
+fn test_dropless_total_allocations() {
+    let mut arena = DroplessArena::default();
+    assert_eq!(arena.total_allocations(), 0);
+    for i in 0..30u64 {
+        arena.alloc(i);
+    }
+    for _ in 0..10 {
+        arena.alloc_slice(&[1u8, 2, 3]);
+    }
+    for _ in 0..10 {
+        arena.alloc_str("hello");
+    }
+    // Empty slices don't allocate.
+    arena.alloc_uninit_slice::<u8>(0);
+    assert_eq!(arena.total_allocations(), 50);
+    assert_eq!(arena.allocations_since_clear(), 50);
+
+    arena.clear();
+    assert_eq!(arena.allocations_since_clear(), 0);
+    arena.alloc(1u32);
+    assert_eq!(arena.total_allocations(), 51);
+    assert_eq!(arena.allocations_since_clear(), 1);
+}
+
+#[test]
+fn test_typed_arena_capacity() {
+    let arena: TypedArena<Point> = TypedArena::with_capacity(100);
+    assert!(arena.capacity() >= 100);
+    assert_eq!(arena.len(), 0);
+
+    let arena: TypedArena<Point> = TypedArena::default();
+    assert_eq!(arena.capacity(), 0);
+    for x in 0..1000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+        assert!(arena.capacity() >= arena.len());
+    }
+    let capacity = arena.capacity();
+    arena.reserve(capacity - arena.len());
+    assert_eq!(arena.capacity(), capacity);
+
+    assert_eq!(TypedArena::<()>::default().capacity(), usize::MAX);
+}
+
+#[test]
+fn test_config_max_chunk_bytes() {
+    let config = ArenaConfig {
+        max_chunk_bytes: 64 * 1024,
+        ..ArenaConfig::default()
+    };
+    let arena: TypedArena<Point> = TypedArena::with_config(config);
+    for x in 0..100_000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+    }
+    let chunks = arena.chunks.borrow();
+    assert!(chunks.len() > 5);
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk.storage.len() * size_of::<Point>() <= 64 * 1024)
+    );
+    drop(chunks);
+
+    let arena = DroplessArena::with_config(config);
+    for x in 0..100_000u64 {
+        arena.alloc(x);
+    }
+    let chunks = arena.chunks.borrow();
+    assert!(chunks.len() > 5);
+    assert!(chunks.iter().all(|chunk| chunk.storage.len() <= 64 * 1024));
+}
+
+#[test]
+fn test_config_growth_factor() {
+    let config = ArenaConfig {
+        growth_factor: 1.5,
+        ..ArenaConfig::default()
+    };
+    let arena = DroplessArena::with_config(config);
+    while arena.chunks.borrow().len() < 4 {
+        arena.alloc(0u64);
+    }
+    let sizes: Vec<usize> = arena
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len())
+        .collect();
+    assert_eq!(sizes, [4096, 6144, 9216, 13824]);
+
+    let arena: TypedArena<u8> = TypedArena::with_config(config);
+    while arena.chunks.borrow().len() < 4 {
+        arena.alloc(0);
+    }
+    let sizes: Vec<usize> = arena
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len())
+        .collect();
+    assert_eq!(sizes, [4096, 6144, 9216, 13824]);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    #[cfg(not(miri))]
+    const N: usize = 1_000_000;
+    #[cfg(miri)]
+    const N: usize = 1000;
+
+    let mut arena: TypedArena<usize> = TypedArena::default();
+    for i in 0..N {
+        arena.alloc(i);
+    }
+    arena.clear();
+    assert!(arena.memory_reserved() > 0);
+    arena.shrink_to_fit();
+    assert_eq!(arena.memory_reserved(), 0);
+    assert_eq!(*arena.alloc(1), 1);
+
+    // Trailing empty chunks are freed, while the objects before them stay intact.
+    arena.alloc(2);
+    arena.reserve(100_000);
+    let chunk_count = arena.chunks.borrow().len();
+    arena.shrink_to_fit();
+    assert_eq!(arena.chunks.borrow().len(), chunk_count - 1);
+    arena.alloc(3);
+    assert!(arena.iter().copied().eq([1, 2, 3]));
+
+    let mut arena = DroplessArena::default();
+    for i in 0..N {
+        arena.alloc(i);
+    }
+    arena.clear();
+    arena.shrink_to_fit();
+    assert_eq!(arena.memory_reserved(), 0);
+    assert_eq!(*arena.alloc(1u32), 1);
+
+    // A chunk that is in use is kept.
+    arena.alloc(2u64);
+    arena.shrink_to_fit();
+    assert_eq!(arena.chunks.borrow().len(), 1);
+    assert_eq!(arena.memory_used(), 2 * size_of::<u64>());
+}
+
+#[test]
+fn test_dropless_shrink_to() {
+    let mut arena = DroplessArena::default();
+    while arena.chunks.borrow().len() < 4 {
+        arena.alloc_slice(&[0u8; 1024]);
+    }
+    arena.clear();
+    arena.shrink_to(64 * 1024);
+    assert!(arena.memory_reserved() <= 64 * 1024);
+
+    // The chunk that `clear` keeps is freed if it is larger than wanted.
+    arena.alloc_slice(&[0u8; 100 * 1024]);
+    arena.clear();
+    assert!(arena.memory_reserved() > 64 * 1024);
+    arena.shrink_to(usize::MAX);
+    assert!(arena.memory_reserved() > 64 * 1024);
+    arena.shrink_to(64 * 1024);
+    assert_eq!(arena.memory_reserved(), 0);
+
+    // Chunks in use are kept, but empty chunks after them are freed.
+    assert_eq!(*arena.alloc(1u64), 1);
+    let reserved = arena.memory_reserved();
+    arena.try_reserve(1024 * 1024).unwrap();
+    arena.shrink_to(reserved);
+    assert_eq!(arena.memory_reserved(), reserved);
+    arena.shrink_to(0);
+    assert_eq!(arena.memory_reserved(), reserved);
+    assert_eq!(arena.memory_used(), size_of::<u64>());
+}
+
+#[cfg(all(feature = "debug-checks", not(feature = "zeroize")))]
+#[cfg_attr(miri, ignore)] // Reading through the stale pointers violates the aliasing model.
+#[test]
+fn test_debug_checks_poison() {
+    let poisoned = u64::from_ne_bytes([0xDD; 8]);
+
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    for i in 0..1000 {
+        arena.alloc(i);
+    }
+    let last = arena.alloc(2) as *const u64;
+    arena.clear();
+    // The last chunk is kept, so this still reads memory owned by the arena.
+    assert_eq!(unsafe { last.read() }, poisoned);
+
+    let mut arena = DroplessArena::default();
+    let value = arena.alloc(3u64) as *const u64;
+    arena.clear();
+    assert_eq!(unsafe { value.read() }, poisoned);
+}
+
+#[cfg(feature = "zeroize")]
+#[cfg_attr(miri, ignore)] // Reading through the stale pointers violates the aliasing model.
+#[test]
+fn test_zeroize() {
+    const SECRET: u64 = 0x5EC2_E75E_C2E7_5EC2;
+
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    for _ in 0..1000 {
+        arena.alloc(SECRET);
+    }
+    let last = arena.alloc(SECRET) as *const u64;
+    arena.clear();
+    // The last chunk is kept, so this still reads memory owned by the arena.
+    assert_eq!(unsafe { last.read() }, 0);
+
+    let mut arena = DroplessArena::default();
+    let secrets = arena.alloc_slice(&[SECRET; 16]).as_ptr();
+    let before = arena.alloc(SECRET) as *const u64;
+    let checkpoint = arena.checkpoint();
+    let after = arena.alloc(SECRET) as *const u64;
+    unsafe { arena.rewind(checkpoint) };
+    assert_eq!(unsafe { after.read() }, 0);
+    assert_eq!(unsafe { before.read() }, SECRET);
+    assert!(arena.contains_ptr(secrets));
+    arena.clear();
+    assert_eq!(unsafe { before.read() }, 0);
+    assert!((0..16).all(|i| unsafe { secrets.add(i).read() } == 0));
+
+    // Chunks that are freed by `rewind` are scrubbed before they are deallocated.
+    let arena = DroplessArena::default();
+    arena.alloc(1u64);
+    let checkpoint = arena.checkpoint();
+    while arena.chunks.borrow().len() < 3 {
+        arena.alloc_slice(&[SECRET; 64]);
+    }
+    let ((), found) = count_freed(SECRET, || unsafe { arena.rewind(checkpoint) });
+    assert_eq!(found, 0);
+    assert_eq!(arena.chunks.borrow().len(), 1);
+    assert_eq!(arena.memory_used(), size_of::<u64>());
+}
+
+#[test]
+fn test_debug() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    for x in 0..1000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+    }
+    let chunk_count = arena.chunks.borrow().len();
+    let debug = format!("{arena:?}");
+    assert!(debug.starts_with("TypedArena {"));
+    assert!(debug.contains(&format!("chunk_count: {chunk_count}")));
+
+    let arena = DroplessArena::default();
+    arena.alloc(1u32);
+    let debug = format!("{arena:?}");
+    assert!(debug.starts_with("DroplessArena {"));
+    assert!(debug.contains("chunk_count: 1"));
+
+    let arena = DropArena::default();
+    arena.alloc("hello".to_string());
+    let debug = format!("{arena:?}");
+    assert!(debug.contains("destructors: 1"));
+    assert!(debug.contains("chunk_count: 1"));
+}
+
+#[test]
+fn test_typed_arena_alloc_with() {
+    #[cfg(not(miri))]
+    const N: usize = 1 << 20;
+    #[cfg(miri)]
+    const N: usize = 1 << 10;
+
+    // Without optimizations the array does end up on the stack, possibly several times, so give
+    // the test some room.
+    std::thread::Builder::new()
+        .stack_size(16 * N)
+        .spawn(|| {
+            let arena: TypedArena<[u8; N]> = TypedArena::default();
+            let big = arena.alloc_with(|| {
+                let mut array = [0; N];
+                array[N - 1] = 42;
+                array
+            });
+            assert_eq!(big[0], 0);
+            assert_eq!(big[N - 1], 42);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_alloc_default() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    assert_eq!(*arena.alloc_default(), Point { x: 0, y: 0, z: 0 });
+
+    let mut arena: TypedArena<String> = TypedArena::default();
+    arena.alloc_default().push_str("hello");
+    assert_eq!(arena.alloc_default(), "");
+    assert_eq!(arena.iter_mut().next().unwrap(), "hello");
+    arena.clear();
+    assert!(arena.is_empty());
+
+    let counter = Cell::new(0);
+    #[derive(Default)]
+    struct Tracked<'a>(Option<DropCounter<'a>>);
+    let mut arena: TypedArena<Tracked<'_>> = TypedArena::default();
+    arena.alloc_default().0 = Some(DropCounter { count: &counter });
+    arena.alloc_default();
+    arena.clear();
+    assert_eq!(counter.get(), 1);
+
+    let arena = DroplessArena::default();
+    assert_eq!(arena.alloc_slice_default::<u32>(16), &[0; 16]);
+    assert!(arena.alloc_slice_default::<u32>(0).is_empty());
+    let point: &mut Point = arena.alloc_default();
+    assert_eq!(*point, Point { x: 0, y: 0, z: 0 });
+    assert_eq!(*arena.alloc_default::<[u64; 4]>(), [0; 4]);
+}
+
+#[test]
+fn test_typed_arena_alloc_uninit() {
+    struct Node<'a> {
+        value: u32,
+        /// Points to `value`.
+        value_ref: *const u32,
+        counter: DropCounter<'a>,
+    }
+
+    let counter = Cell::new(0);
+    let mut arena = TypedArena::default();
+    arena.alloc(Node {
+        value: 1,
+        value_ref: ptr::null(),
+        counter: DropCounter { count: &counter },
+    });
+    let slot = arena.alloc_uninit();
+    let node = slot.as_mut_ptr();
+    // SAFETY: The fields are written through the slot's pointer, which stays valid.
+    unsafe {
+        (&raw mut (*node).value).write(2);
+        (&raw mut (*node).value_ref).write(&raw const (*node).value);
+        (&raw mut (*node).counter).write(DropCounter { count: &counter });
+    }
+    // SAFETY: The slot has been initialized.
+    let node = unsafe { arena.assume_init_entry(slot) };
+    assert!(ptr::eq(node.value_ref, &node.value));
+    assert_eq!(
+        arena.iter().map(|node| node.value).collect::<Vec<_>>(),
+        [1, 2]
+    );
+    arena.clear();
+    assert_eq!(counter.get(), 2);
+
+    // Until the slot is registered, the objects can't be read, and they are leaked.
+    arena.alloc(Node {
+        value: 3,
+        value_ref: ptr::null(),
+        counter: DropCounter { count: &counter },
+    });
+    arena.alloc_uninit();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| arena.iter().count()));
+    assert!(result.is_err());
+    arena.clear();
+    assert_eq!(counter.get(), 2);
+    assert!(arena.is_empty());
+    arena.alloc(Node {
+        value: 4,
+        value_ref: ptr::null(),
+        counter: DropCounter { count: &counter },
+    });
+    assert_eq!(arena.iter().count(), 1);
+    drop(arena);
+    assert_eq!(counter.get(), 3);
+}
+
+#[test]
+fn test_typed_arena_alloc_with_panic() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    arena.alloc(Point { x: 1, y: 0, z: 0 });
+    let result = panic::catch_unwind(AssertUnwindSafe(|| arena.alloc_with(|| panic!("boom"))));
+    assert!(result.is_err());
+    assert_eq!(arena.stats().bytes_used, size_of::<Point>());
+    assert_eq!(arena.alloc_with(|| Point { x: 2, y: 0, z: 0 }).x, 2);
+}
+
+/// Runs `f`, which must panic, and returns the panic message.
+#[cfg(not(miri))]
+fn panic_message(f: impl FnOnce()) -> String {
+    let payload = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload.downcast_ref::<String>().unwrap().clone(),
+    }
+}
+
+/// Runs `f`, which must panic, and returns the file and line that the panic was reported at.
+#[cfg(not(miri))]
+fn panic_location(f: impl FnOnce()) -> (String, u32) {
+    thread_local! {
+        static LOCATION: RefCell<Option<(String, u32)>> = const { RefCell::new(None) };
+    }
+    static INSTALL_HOOK: std::sync::Once = std::sync::Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(std::boxed::Box::new(move |info| {
+            if let Some(location) = info.location() {
+                let location = (location.file().to_string(), location.line());
+                LOCATION.with(|slot| *slot.borrow_mut() = Some(location));
+            }
+            default_hook(info);
+        }));
+    });
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+    LOCATION.with(|slot| slot.borrow_mut().take()).unwrap()
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_allocation_panic_location() {
+    // The size checks panic inside the crate, but `#[track_caller]` makes the panics point here.
+    let arena = DroplessArena::default();
+    let (file, line) = panic_location(|| {
+        arena.alloc_slice_fill_copy(usize::MAX / 2, 0u32);
+    });
+    assert_eq!((file.as_str(), line), (file!(), line!() - 2));
+
+    let arena = SyncDroplessArena::default();
+    let (file, line) = panic_location(|| {
+        arena.alloc_raw(Layout::from_size_align(isize::MAX as usize, 1).unwrap());
+    });
+    assert_eq!((file.as_str(), line), (file!(), line!() - 2));
+
+    #[cfg(feature = "from-iter")]
+    {
+        crate::declare_arena!([]);
+        let arena = Arena::default();
+        let (file, line) = panic_location(|| {
+            arena.alloc_from_iter(std::iter::repeat_n(0u32, usize::MAX / 2));
+        });
+        assert_eq!((file.as_str(), line), (file!(), line!() - 2));
+    }
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_allocation_too_large() {
+    let arena = DroplessArena::default();
+    let message = panic_message(|| {
+        arena.alloc_slice_fill_copy(usize::MAX / 2, 0u32);
+    });
+    assert_eq!(
+        message,
+        format!(
+            "arena allocation too large: {} bytes for {} objects of type `u32`",
+            (usize::MAX / 2) as u128 * 4,
+            usize::MAX / 2
+        )
+    );
+    let message = panic_message(|| {
+        arena.alloc_raw(Layout::from_size_align(isize::MAX as usize, 1).unwrap());
+    });
+    assert_eq!(
+        message,
+        format!("arena allocation too large: {} bytes", isize::MAX)
+    );
+    let message = panic_message(|| drop(DroplessArena::with_capacity(usize::MAX)));
+    assert_eq!(
+        message,
+        format!("arena allocation too large: {} bytes", usize::MAX)
+    );
+    assert_eq!(arena.chunks.borrow().len(), 0);
+
+    let arena: TypedArena<u64> = TypedArena::default();
+    let message = panic_message(|| arena.reserve(usize::MAX / 4));
+    assert_eq!(
+        message,
+        format!(
+            "arena allocation too large: {} bytes for {} objects of type `u64`",
+            (usize::MAX / 4) as u128 * 8,
+            usize::MAX / 4
+        )
+    );
+    let message = panic_message(|| drop(TypedArena::<u64>::with_capacity(usize::MAX)));
+    assert!(message.contains(&format!("{} bytes", usize::MAX as u128 * 8)));
+    assert_eq!(arena.chunks.borrow().len(), 0);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_slice_length_overflow() {
+    // `usize::MAX / 4 * size_of::<u64>()` wraps around.
+    const LEN: usize = usize::MAX / 4;
+    fn assert_too_large(f: impl FnOnce()) {
+        assert_eq!(
+            panic_message(f),
+            format!(
+                "arena allocation too large: {} bytes for {LEN} objects of type `u64`",
+                LEN as u128 * 8
+            )
+        );
+    }
+
+    let arena = DroplessArena::default();
+    assert_too_large(|| {
+        arena.alloc_slice_fill_copy(LEN, 0u64);
+    });
+    assert_too_large(|| {
+        arena.alloc_slice_fill_with(LEN, |_| 0u64);
+    });
+    assert_too_large(|| {
+        arena.alloc_uninit_slice::<u64>(LEN);
+    });
+    assert_too_large(|| {
+        // SAFETY: All-zero bytes are a valid `u64`.
+        unsafe { arena.alloc_slice_zeroed::<u64>(LEN) };
+    });
+    assert_eq!(arena.chunks.borrow().len(), 0);
+    assert_eq!(arena.alloc_slice(&[1u64, 2]), &[1, 2]);
+}
+
+#[test]
+fn test_indexed_arena() {
+    #[cfg(not(miri))]
+    const N: i32 = 1000;
+    #[cfg(miri)]
+    const N: i32 = 100;
+
+    let arena: IndexedArena<Point> = IndexedArena::default();
+    assert!(arena.is_empty());
+    let indices: Vec<_> = (0..N)
+        .map(|x| arena.push(Point { x, y: x * 2, z: 0 }))
+        .collect();
+    assert_eq!(arena.len(), N as usize);
+    assert!(arena.chunk_starts.borrow().len() > 1);
+
+    // Visit the indices in a scrambled order; 7919 is prime, so this is a permutation.
+    let mut seen = HashSet::new();
+    for i in 0..N {
+        let idx = indices[(i * 7919 % N) as usize];
+        let point = arena.get(idx);
+        assert_eq!(point.x as usize, idx.index());
+        assert_eq!(point.y, point.x * 2);
+        seen.insert(idx);
+    }
+    assert_eq!(seen.len(), N as usize);
+}
+
+#[test]
+fn test_indexed_arena_index() {
+    let mut arena: IndexedArena<String> = IndexedArena::default();
+    let indices: Vec<Idx> = (0..100).map(|i| arena.push(i.to_string())).collect();
+    assert_eq!(arena[indices[42]], "42");
+    arena[indices[42]].push('!');
+    assert_eq!(arena.get(indices[42]), "42!");
+
+    let mut count = 0;
+    for (i, (idx, value)) in arena.iter_indexed().enumerate() {
+        assert_eq!(idx, indices[i]);
+        assert_eq!(*value, arena[idx]);
+        count += 1;
+    }
+    assert_eq!(count, 100);
+    assert!(
+        IndexedArena::<u8>::default()
+            .iter_indexed()
+            .next()
+            .is_none()
+    );
+}
+
+#[test]
+#[should_panic(expected = "index out of bounds")]
+fn test_indexed_arena_out_of_bounds() {
+    let first: IndexedArena<u32> = IndexedArena::default();
+    let second: IndexedArena<u32> = IndexedArena::default();
+    first.push(1);
+    let idx = first.push(2);
+    second.push(3);
+    second.get(idx);
+}
+
+#[test]
+fn test_typed_arena_reserve() {
+    let arena: TypedArena<Point> = TypedArena::default();
+    arena.alloc(Point { x: 0, y: 0, z: 0 });
+    arena.reserve(5000);
+    let chunk_count = arena.chunks.borrow().len();
+    for x in 0..5000 {
+        arena.alloc(Point { x, y: 0, z: 0 });
+    }
+    assert_eq!(arena.chunks.borrow().len(), chunk_count);
+    // There is room already, so this doesn't allocate.
+    arena.reserve(0);
+    assert_eq!(arena.chunks.borrow().len(), chunk_count);
+}
+
+#[test]
+fn test_arena_alloc_nested() {
+    struct Inner {
+        value: u8,
+    }
+    struct Outer<'a> {
+        inner: &'a Inner,
+    }
+    enum EI<'e> {
+        I(Inner),
+        O(Outer<'e>),
+    }
+
+    struct Wrap(DroplessArena);
+
+    impl Wrap {
+        fn alloc_inner<F: Fn() -> Inner>(&self, f: F) -> &Inner {
+            match self.0.alloc(EI::I(f())) {
+                EI::I(i) => i,
+                _ => panic!("mismatch"),
+            }
+        }
+        fn alloc_outer<'a, F: Fn() -> Outer<'a>>(&'a self, f: F) -> &'a Outer<'a> {
+            match self.0.alloc(EI::O(f())) {
+                EI::O(o) => o,
+                _ => panic!("mismatch"),
+            }
+        }
+    }
+
+    let arena = Wrap(DroplessArena::default());
+
+    let result = arena.alloc_outer(|| Outer {
+        inner: arena.alloc_inner(|| Inner { value: 10 }),
+    });
+
+    assert_eq!(result.inner.value, 10);
+}
+
+#[test]
+fn test_copy() {
+    let arena = TypedArena::default();
+    #[cfg(not(miri))]
+    const N: usize = 100000;
+    #[cfg(miri)]
+    const N: usize = 1000;
+    for _ in 0..N {
+        arena.alloc(Point { x: 1, y: 2, z: 3 });
+    }
+}
+
+// #[bench]
+// fn bench_copy(b: &mut Bencher) {
+//     let arena = TypedArena::default();
+//     b.iter(|| arena.alloc(Point { x: 1, y: 2, z: 3 }))
+// }
+//
+// #[bench]
+// fn bench_copy_nonarena(b: &mut Bencher) {
+//     b.iter(|| {
+//         let _: Box<_> = Box::new(Point { x: 1, y: 2, z: 3 });
+//     })
+// }
+
+#[allow(dead_code)]
+struct Noncopy {
+    string: String,
+    array: Vec<i32>,
+}
+
+#[test]
+fn test_noncopy() {
+    let arena = TypedArena::default();
+    #[cfg(not(miri))]
+    const N: usize = 100000;
+    #[cfg(miri)]
+    const N: usize = 1000;
+    for _ in 0..N {
+        arena.alloc(Noncopy {
+            string: "hello world".to_string(),
+            array: vec![1, 2, 3, 4, 5],
+        });
+    }
+}
+
+#[test]
+fn test_typed_arena_clear() {
+    let mut arena = TypedArena::default();
+    for _ in 0..10 {
+        arena.clear();
+        #[cfg(not(miri))]
+        const N: usize = 10000;
+        #[cfg(miri)]
+        const N: usize = 100;
+        for _ in 0..N {
+            arena.alloc(Point { x: 1, y: 2, z: 3 });
+        }
+    }
+}
+
+#[test]
+fn test_typed_arena_clear_with() {
+    let counter = Cell::new(0);
+    for policy in [
+        ClearPolicy::KeepLongest,
+        ClearPolicy::KeepFirst,
+        ClearPolicy::KeepNone,
+    ] {
+        counter.set(0);
+        let mut arena = TypedArena::default();
+        for _ in 0..1000 {
+            arena.alloc(DropCounter { count: &counter });
+        }
+        let chunks = arena.chunks.borrow().len();
+        assert!(chunks > 1);
+        let element_size = size_of::<DropCounter<'_>>();
+        let first = arena.chunks.borrow()[0].storage.len() * element_size;
+        let longest = arena.chunks.borrow()[chunks - 1].storage.len() * element_size;
+        arena.clear_with(policy);
+        assert_eq!(counter.get(), 1000);
+        assert!(arena.is_empty());
+        let expected = match policy {
+            ClearPolicy::KeepLongest => longest,
+            ClearPolicy::KeepFirst => first,
+            ClearPolicy::KeepNone => 0,
+        };
+        assert_eq!(arena.memory_reserved(), expected);
+        // The kept chunk, if any, is reused.
+        arena.alloc(DropCounter { count: &counter });
+        assert_eq!(arena.len(), 1);
+        assert_eq!(
+            arena.memory_reserved() == expected,
+            policy != ClearPolicy::KeepNone
+        );
+    }
+}
+
+// #[bench]
+// fn bench_typed_arena_clear(b: &mut Bencher) {
+//     let mut arena = TypedArena::default();
+//     b.iter(|| {
+//         arena.alloc(Point { x: 1, y: 2, z: 3 });
+//         arena.clear();
+//     })
+// }
+//
+// #[bench]
+// fn bench_typed_arena_clear_100(b: &mut Bencher) {
+//     let mut arena = TypedArena::default();
+//     b.iter(|| {
+//         for _ in 0..100 {
+//             arena.alloc(Point { x: 1, y: 2, z: 3 });
+//         }
+//         arena.clear();
+//     })
+// }
+
+// Drop tests
+
+struct DropCounter<'a> {
+    count: &'a Cell<u32>,
+}
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        self.count.set(self.count.get() + 1);
+    }
+}
+
+#[test]
+fn test_typed_arena_drop_count() {
+    let counter = Cell::new(0);
+    {
+        let arena: TypedArena<DropCounter<'_>> = TypedArena::default();
+        for _ in 0..100 {
+            // Allocate something with drop glue to make sure it doesn't leak.
+            arena.alloc(DropCounter { count: &counter });
+        }
+    };
+    assert_eq!(counter.get(), 100);
+}
+
+#[test]
+fn test_typed_arena_drop_on_clear() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<DropCounter<'_>> = TypedArena::default();
+    for i in 0..10 {
+        for _ in 0..100 {
+            // Allocate something with drop glue to make sure it doesn't leak.
+            arena.alloc(DropCounter { count: &counter });
+        }
+        arena.clear();
+        assert_eq!(counter.get(), i * 100 + 100);
+    }
+}
+
+thread_local! {
+    static DROP_COUNTER: Cell<u32> = const { Cell::new(0) }
+}
+
+#[allow(unused)]
+struct SmallDroppable(u8);
+
+impl Drop for SmallDroppable {
+    fn drop(&mut self) {
+        DROP_COUNTER.with(|c| c.set(c.get() + 1));
+    }
+}
+
+#[test]
+fn test_typed_arena_drop_small_count() {
+    DROP_COUNTER.with(|c| c.set(0));
+    {
+        let arena: TypedArena<SmallDroppable> = TypedArena::default();
+        for _ in 0..100 {
+            // Allocate something with drop glue to make sure it doesn't leak.
+            arena.alloc(SmallDroppable(0));
+        }
+        // dropping
+    };
+    assert_eq!(DROP_COUNTER.with(|c| c.get()), 100);
+}
+
+// #[bench]
+// fn bench_noncopy(b: &mut Bencher) {
+//     let arena = TypedArena::default();
+//     b.iter(|| {
+//         arena.alloc(Noncopy { string: "hello world".to_string(), array: vec![1, 2, 3, 4, 5] })
+//     })
+// }
+//
+// #[bench]
+// fn bench_noncopy_nonarena(b: &mut Bencher) {
+//     b.iter(|| {
+//         let _: Box<_> =
+//             Box::new(Noncopy { string: "hello world".to_string(), array: vec![1, 2, 3, 4, 5] });
+//     })
+// }
+
+struct WithInternalRef<'a> {
+    number: u32,
+    next: Option<&'a WithInternalRef<'a>>,
+}
+
+#[test]
+fn test_dropless_with_reference() {
+    let arena = DroplessArena::default();
+    let second = arena.alloc(WithInternalRef {
+        number: 2,
+        next: None,
+    });
+    let first = arena.alloc(WithInternalRef {
+        number: 1,
+        next: Some(second),
+    });
+    assert_eq!(first.number, 1);
+    assert_eq!(first.next.unwrap().number, 2);
+}
+
+#[test]
+fn test_dropless_alloc_raw() {
+    let arena = DroplessArena::default();
+    let layouts = [
+        Layout::new::<u8>(),
+        Layout::new::<u64>(),
+        Layout::new::<[u16; 3]>(),
+        Layout::from_size_align(100, 16).unwrap(),
+        Layout::new::<PageAligned>(),
+        Layout::from_size_align(1, 1).unwrap(),
+    ];
+    let mut ptrs = vec![];
+    for layout in layouts {
+        let ptr = arena.alloc_raw(layout);
+        assert_eq!(ptr.addr().get() % layout.align(), 0);
+        // The memory must be writable.
+        unsafe { ptr.write_bytes(0xAB, layout.size()) };
+        assert!(arena.contains_ptr(ptr.as_ptr()));
+        ptrs.push((ptr, layout));
+    }
+    for (ptr, layout) in ptrs {
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), layout.size()) };
+        assert!(bytes.iter().all(|&b| b == 0xAB));
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_typed_arena_alloc_zst() {
+    TypedArena::<()>::default().alloc(());
+}
+
+#[test]
+#[should_panic]
+fn test_dropless_alloc_needs_drop() {
+    DroplessArena::default().alloc(String::from("leaked"));
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+#[should_panic]
+fn test_dropless_alloc_from_iter_needs_drop() {
+    DroplessArena::default().alloc_from_iter([String::from("leaked")]);
+}
+
+#[test]
+#[should_panic]
+fn test_dropless_alloc_raw_zero_size() {
+    let arena = DroplessArena::default();
+    arena.alloc_raw(Layout::new::<()>());
+}
+
+#[test]
+fn test_sync_dropless_alloc_raw() {
+    let arena = SyncDroplessArena::default();
+    for layout in [
+        Layout::new::<u8>(),
+        Layout::from_size_align(100, 16).unwrap(),
+        Layout::new::<PageAligned>(),
+    ] {
+        let ptr = arena.alloc_raw(layout);
+        assert_eq!(ptr.addr().get() % layout.align(), 0);
+        // The memory must be writable.
+        unsafe { ptr.write_bytes(0xAB, layout.size()) };
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_sync_dropless_alloc_raw_zero_size() {
+    SyncDroplessArena::default().alloc_raw(Layout::new::<()>());
+}
+
+#[test]
+#[should_panic]
+fn test_sync_dropless_alloc_zst() {
+    SyncDroplessArena::default().alloc(());
+}
+
+#[test]
+fn test_dropless_slice() {
+    let arena = DroplessArena::default();
+    let slice = arena.alloc_slice(&[1, 2, 3, 4, 5]);
+    assert_eq!(slice, &[1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn test_dropless_alloc_bytes() {
+    let arena = DroplessArena::default();
+    for align in [1, 8, 64] {
+        // Misalign the bump pointer first.
+        arena.alloc(1u8);
+        let bytes = arena.alloc_bytes(13, align);
+        assert_eq!(bytes.len(), 13);
+        assert_eq!(bytes.as_ptr().addr() % align, 0);
+        for byte in bytes.iter_mut() {
+            byte.write(0xAB);
+        }
+    }
+    assert!(arena.alloc_bytes(0, 16).is_empty());
+}
+
+#[derive(Clone, Copy)]
+#[repr(align(4096))]
+struct PageAligned(u8);
+
+#[test]
+fn test_over_aligned() {
+    let typed: TypedArena<PageAligned> = TypedArena::default();
+    let dropless = DroplessArena::default();
+    for i in 0..10 {
+        let a = typed.alloc(PageAligned(i));
+        assert_eq!((a as *mut PageAligned).addr() % 4096, 0);
+        // Misalign the bump pointer first.
+        dropless.alloc(i);
+        let b = dropless.alloc(PageAligned(i));
+        assert_eq!((b as *mut PageAligned).addr() % 4096, 0);
+        assert_eq!((a.0, b.0), (i, i));
+    }
+}
+
+#[test]
+fn test_dropless_alloc_zeroed() {
+    let arena = DroplessArena::default();
+    // Leave garbage behind and misalign the bump pointer.
+    arena.alloc_slice(&[0xFFu8; 3]);
+    let array: &mut [u64; 16] = unsafe { arena.alloc_zeroed() };
+    assert_eq!(*array, [0; 16]);
+    assert_eq!((array as *mut [u64; 16]).addr() % align_of::<u64>(), 0);
+
+    arena.alloc_slice(&[0xFFu8; 3]);
+    let slice: &mut [[u64; 16]] = unsafe { arena.alloc_slice_zeroed(5) };
+    assert_eq!(slice, &[[0; 16]; 5]);
+    assert_eq!(slice.as_ptr().addr() % align_of::<u64>(), 0);
+    assert!(unsafe { arena.alloc_slice_zeroed::<u64>(0) }.is_empty());
+}
+
+#[test]
+fn test_dropless_alloc_slice_fill() {
+    let arena = DroplessArena::default();
+    let squares = arena.alloc_slice_fill_with(10, |i| i * i);
+    assert_eq!(squares, &[0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+    let sevens = arena.alloc_slice_fill_copy(5, 7u8);
+    assert_eq!(sevens, &[7; 5]);
+    assert!(arena.alloc_slice_fill_copy(0, 7u8).is_empty());
+}
+
+#[test]
+fn test_dropless_contains_ptr() {
+    let arena = DroplessArena::default();
+    let on_stack = 5u64;
+    assert!(!arena.contains_ptr(&on_stack));
+
+    let first = arena.alloc(1u64) as *const u64;
+    let slice = arena.alloc_slice(&[1u8, 2, 3]) as *const [u8];
+    // Spill into further chunks.
+    for i in 0..10_000u64 {
+        arena.alloc(i);
+    }
+    let last = arena.alloc(2u64) as *const u64;
+    assert!(arena.contains_ptr(first));
+    assert!(arena.contains_ptr(slice));
+    assert!(arena.contains_ptr(last));
+    assert!(!arena.contains_ptr(&on_stack));
+    assert!(!arena.contains_ptr(ptr::null::<u8>()));
+}
+
+#[test]
+fn test_dropless_huge_slice() {
+    #[cfg(not(miri))]
+    const N: usize = 8 * 1024 * 1024;
+    #[cfg(miri)]
+    const N: usize = 64 * 1024;
+    let bytes: Vec<u8> = (0..N).map(|i| i as u8).collect();
+    let arena = DroplessArena::default();
+    let slice = arena.alloc_slice(&bytes);
+    assert_eq!(slice, &bytes[..]);
+    // The slice got a chunk of its own.
+    assert_eq!(arena.chunks.borrow().len(), 1);
+    assert!(arena.stats().bytes_reserved >= N);
+}
+
+#[test]
+fn test_dropless_alloc_slice_from_iter() {
+    let arena = DroplessArena::with_capacity(4096);
+    let expected: Vec<u32> = (0..500).map(|x| x * 2).collect();
+    let (slice, allocations) =
+        count_allocations(|| arena.alloc_slice_from_iter((0u32..500).map(|x| x * 2)));
+    assert_eq!(slice, &expected[..]);
+    // The elements were written into the arena directly, without a temporary `Vec`.
+    assert_eq!(allocations, 0);
+
+    // `filter` hides the exact length, so the elements are buffered first.
+    let slice = arena.alloc_slice_from_iter((0u32..500).map(|x| x * 2).filter(|_| true));
+    assert_eq!(slice, &expected[..]);
+    assert!(
+        arena
+            .alloc_slice_from_iter(core::iter::empty::<u32>())
+            .is_empty()
+    );
+    assert!(
+        arena
+            .alloc_slice_from_iter((0u32..5).filter(|_| false))
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_dropless_alloc_uninit_slice() {
+    let arena = DroplessArena::default();
+    let uninit = arena.alloc_uninit_slice::<u32>(10);
+    assert_eq!(uninit.len(), 10);
+    assert_eq!(uninit.as_ptr().addr() % align_of::<u32>(), 0);
+    for (i, elem) in uninit.iter_mut().enumerate() {
+        elem.write(i as u32 * 3);
+    }
+    let init: &[u32] = unsafe { uninit.assume_init_mut() };
+    assert!(init.iter().copied().eq((0..10).map(|i| i * 3)));
+    assert!(arena.alloc_uninit_slice::<u64>(0).is_empty());
+}
+
+#[test]
+fn test_dropless_str() {
+    let arena = DroplessArena::default();
+    let string = arena.alloc_str("hello world");
+    assert_eq!(string, "hello world");
+}
+
+#[test]
+fn test_dropless_alloc_strs() {
+    let arena = DroplessArena::default();
+    let owned = vec![String::from("a"), String::from("bb"), String::from("ccc")];
+    let strs = arena.alloc_strs(owned.iter().map(String::as_str));
+    drop(owned);
+    assert_eq!(strs, &["a", "bb", "ccc"]);
+    assert!(strs.iter().all(|s| arena.contains_ptr(s.as_ptr())));
+    assert!(arena.contains_ptr(strs.as_ptr()));
+
+    // Iterators without an exact length work as well, and so do empty strings.
+    let strs = arena.alloc_strs("x,,yz".split(','));
+    assert_eq!(strs, &["x", "", "yz"]);
+    assert!(arena.alloc_strs([]).is_empty());
+}
+
+#[test]
+fn test_dropless_str_from_bytes() {
+    let arena = DroplessArena::default();
+    let big = "ü".repeat(10_000);
+    let string = arena.alloc_str_from_bytes(big.as_bytes()).unwrap();
+    assert_eq!(string, big);
+    assert!(arena.contains_ptr(string.as_ptr()));
+    assert_eq!(arena.alloc_str_from_bytes(b""), Ok(""));
+
+    let used = arena.memory_used();
+    let err = arena.alloc_str_from_bytes(b"ab\xFFcd").unwrap_err();
+    assert_eq!(err.valid_up_to(), 2);
+    // Nothing was allocated for the invalid bytes.
+    assert_eq!(arena.memory_used(), used);
+}
+
+#[test]
+fn test_dropless_str_mut() {
+    let arena = DroplessArena::default();
+    let original = "HELLO";
+    let string = arena.alloc_str_mut(original);
+    string.make_ascii_lowercase();
+    assert_eq!(string, "hello");
+    assert_eq!(original, "HELLO");
+}
+
+#[test]
+fn test_dropless_c_str() {
+    let arena = DroplessArena::default();
+    let c_str = arena.alloc_c_str("hello");
+    assert_eq!(c_str.to_bytes_with_nul(), b"hello\0");
+    assert!(arena.contains_ptr(c_str.as_ptr()));
+    assert_eq!(arena.alloc_c_str("").to_bytes_with_nul(), b"\0");
+}
+
+#[test]
+fn test_dropless_c_str_interior_nul() {
+    let arena = DroplessArena::default();
+    let err = arena.try_alloc_c_str("hel\0lo").unwrap_err();
+    assert_eq!(err.nul_position(), 3);
+    assert_eq!(arena.stats().bytes_used, 0);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| arena.alloc_c_str("\0")));
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_typed_arena_alloc_slice_clone() {
+    let strings = ["a".to_string(), "bb".to_string(), "ccc".to_string()];
+    let arena: TypedArena<String> = TypedArena::default();
+    let slice = arena.alloc_slice_clone(&strings);
+    assert_eq!(slice, &strings);
+    slice[0].push('!');
+    assert_eq!(slice[0], "a!");
+    assert_eq!(strings[0], "a");
+    assert!(arena.alloc_slice_clone(&[]).is_empty());
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_typed_arena_alloc_from_iter_panic() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<DropCounter<'_>> = TypedArena::default();
+    arena.alloc(DropCounter { count: &counter });
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_from_iter((0..10).map(|i| {
+            if i == 4 {
+                panic!("boom");
+            }
+            DropCounter { count: &counter }
+        }));
+    }));
+    assert!(result.is_err());
+    // The four collected elements were dropped, and none of them ended up in the arena.
+    assert_eq!(counter.get(), 4);
+    assert_eq!(arena.iter().count(), 1);
+    drop(arena);
+    assert_eq!(counter.get(), 5);
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_typed_arena_alloc_from_iter_strings_panic() {
+    let counter = Cell::new(0);
+    let mut arena: TypedArena<(String, DropCounter<'_>)> = TypedArena::default();
+    let make = |i: i32| (i.to_string(), DropCounter { count: &counter });
+    let slice = arena.alloc_from_iter((0..5).map(make));
+    assert_eq!(slice[4].0, "4");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_from_iter((0..5).map(|i| {
+            if i == 2 {
+                panic!("boom");
+            }
+            make(i)
+        }));
+    }));
+    assert!(result.is_err());
+    // The two strings built before the panic were dropped; the ones already in the arena weren't.
+    assert_eq!(counter.get(), 2);
+    assert!(
+        arena
+            .iter()
+            .map(|(s, _)| s.as_str())
+            .eq(["0", "1", "2", "3", "4"])
+    );
+    drop(arena);
+    assert_eq!(counter.get(), 7);
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_alloc_from_iter_capped() {
+    let counter = Cell::new(0);
+    let arena: TypedArena<DropCounter<'_>> = TypedArena::default();
+    let counters = || std::iter::repeat_with(|| DropCounter { count: &counter });
+    let error = arena
+        .alloc_from_iter_capped(counters().take(10), 5)
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error, CapExceeded { max_elems: 5 });
+    assert_eq!(error.to_string(), "iterator yielded more than 5 elements");
+    // The five collected elements were dropped, and so was the sixth that exceeded the cap.
+    assert_eq!(counter.get(), 6);
+    assert!(arena.is_empty());
+    let slice = arena.alloc_from_iter_capped(counters().take(5), 5).unwrap();
+    assert_eq!(slice.len(), 5);
+    assert_eq!(arena.len(), 5);
+
+    let dropless = DroplessArena::default();
+    // The size hint doesn't make it reserve room for the whole iterator.
+    assert!(dropless.alloc_from_iter_capped(0u64.., 100).is_err());
+    assert_eq!(dropless.memory_reserved(), 0);
+    let slice = dropless.alloc_from_iter_capped((0..1000).filter(|i| i % 10 == 0), 100);
+    assert_eq!(slice.unwrap().len(), 100);
+    assert_eq!(
+        dropless.alloc_from_iter_capped(std::iter::empty::<u8>(), 0),
+        Ok(&mut [][..])
+    );
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_dropless_alloc_from_iter_panic() {
+    let arena = DroplessArena::default();
+    for exact in [true, false] {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let iter = (0..10u32).inspect(|&i| {
+                if i == 4 {
+                    panic!("boom");
+                }
+            });
+            // `filter` hides the exact length, so the buffering path is taken.
+            if exact {
+                arena.alloc_from_iter(iter);
+            } else {
+                arena.alloc_from_iter(iter.filter(|_| true));
+            }
+        }));
+        assert!(result.is_err());
+        assert_eq!(arena.alloc_from_iter(0..3u32), &[0, 1, 2]);
+    }
+}
+
+#[cfg(feature = "from-iter")]
+#[derive(Debug, PartialEq, Eq)]
+struct NotCopyNotDrop {
+    value: i32,
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_declare_arena() {
+    crate::declare_arena!([
+        ints: NotCopyNotDrop,
+        boxes: Box<i32>,
+    ]);
+
+    let arena = Arena::default();
+
+    // `Copy` types that you want to allocate don't need to be mentioned in `declare_arena!`.
+    let num = arena.alloc(1);
+    assert_eq!(num, &1);
+
+    let slice = arena.alloc_slice(&[10, 15, 17]);
+    assert_eq!(slice, &[10, 15, 17]);
+
+    let val = arena.alloc(NotCopyNotDrop { value: 2 });
+    assert_eq!(val.value, 2);
+
+    let slice = arena.alloc_from_iter((1..3).map(|n| NotCopyNotDrop { value: n }));
+    assert_eq!(
+        slice,
+        &[NotCopyNotDrop { value: 1 }, NotCopyNotDrop { value: 2 }]
+    );
+
+    let boxed = arena.alloc(Box::new(2));
+    assert_eq!(boxed, &Box::new(2));
+
+    let string = arena.alloc_str("hello world");
+    assert_eq!(string, "hello world");
+}
+
+struct DropRecorder<'a> {
+    id: u32,
+    order: &'a RefCell<Vec<u32>>,
+}
+
+impl Drop for DropRecorder<'_> {
+    fn drop(&mut self) {
+        self.order.borrow_mut().push(self.id);
+    }
+}
+
+#[test]
+fn test_typed_arena_as_slices() {
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    assert_eq!(arena.as_slices().count(), 0);
+    for i in 0..1000 {
+        arena.alloc(i);
+    }
+    arena.alloc(1000);
+    let chunks = arena.chunks.borrow().len();
+    assert!(chunks > 1);
+    assert_eq!(arena.as_slices().count(), chunks);
+    // The last chunk only yields its filled prefix.
+    let last = arena.as_slices().last().unwrap().len();
+    assert!(last < arena.chunks.borrow().last().unwrap().storage.len());
+    let concatenated: Vec<u64> = arena.as_slices().flatten().copied().collect();
+    let iterated: Vec<u64> = arena.iter().copied().collect();
+    assert_eq!(concatenated, iterated);
+    assert_eq!(concatenated, (0..=1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_typed_arena_get() {
+    let mut arena: TypedArena<u64> = TypedArena::default();
+    assert_eq!(arena.get(0), None);
+    for i in 0..1000 {
+        arena.alloc(i);
+    }
+    assert!(arena.chunks.borrow().len() > 1);
+    assert_eq!(arena.get(0), Some(&0));
+    assert_eq!(arena.get(500), Some(&500));
+    assert_eq!(arena.get(999), Some(&999));
+    assert_eq!(arena.get(1000), None);
+
+    // A slice too large for the current chunk leaves it partially filled.
+    arena.alloc_slice(4000, |i| 1000 + i as u64);
+    arena.alloc(5000);
+    assert!((0..=5000).all(|i| arena.get(i as usize) == Some(&i)));
+    assert_eq!(arena.get(5001), None);
+
+    arena.clear();
+    assert_eq!(arena.get(0), None);
+    arena.alloc(7);
+    assert_eq!(arena.get(0), Some(&7));
+    assert_eq!(arena.get(1), None);
+}
+
+#[test]
+fn test_typed_arena_freeze() {
+    let arena: TypedArena<String> = TypedArena::default();
+    for i in 0..1000 {
+        arena.alloc(i.to_string());
+    }
+    let frozen = arena.freeze();
+    assert_eq!(frozen.len(), 1000);
+    assert_eq!(frozen[0], "0");
+    assert_eq!(frozen[500], "500");
+    assert_eq!(frozen[999], "999");
+    assert!(frozen.get(1000).is_none());
+
+    let total = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                frozen
+                    .iter()
+                    .map(|s| s.parse::<u32>().unwrap())
+                    .sum::<u32>()
+            })
+            .join()
+            .unwrap()
+    });
+    assert_eq!(total, (0..1000).sum());
+
+    let counter = Cell::new(0);
+    let arena = TypedArena::default();
+    arena.alloc(DropCounter { count: &counter });
+    arena.alloc(DropCounter { count: &counter });
+    let frozen = arena.freeze();
+    assert_eq!(counter.get(), 0);
+    drop(frozen);
+    assert_eq!(counter.get(), 2);
+
+    let frozen = TypedArena::<u32>::default().freeze();
+    assert!(frozen.is_empty());
+    assert_eq!(frozen.iter().count(), 0);
+}
+
+#[test]
+fn test_zst_drop_count() {
+    thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+    struct ZstWithDrop;
+    impl Drop for ZstWithDrop {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+
+    // `TypedArena` rejects zero-sized types, dropping the object that was passed in.
+    let mut arena = TypedArena::default();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| arena.alloc(ZstWithDrop)));
+    assert!(result.is_err());
+    assert_eq!(DROPS.with(Cell::get), 1);
+    arena.clear();
+    drop(arena);
+    assert_eq!(DROPS.with(Cell::get), 1);
+
+    // `DropArena` accepts them and runs their destructors.
+    DROPS.with(|drops| drops.set(0));
+    let mut arena = DropArena::default();
+    for _ in 0..1000 {
+        arena.alloc(ZstWithDrop);
+    }
+    #[cfg(feature = "from-iter")]
+    assert_eq!(
+        arena.alloc_from_iter((0..1000).map(|_| ZstWithDrop)).len(),
+        1000
+    );
+    assert_eq!(DROPS.with(Cell::get), 0);
+    arena.clear();
+    let expected = if cfg!(feature = "from-iter") {
+        2000
+    } else {
+        1000
+    };
+    assert_eq!(DROPS.with(Cell::get), expected);
+    arena.alloc(ZstWithDrop);
+    drop(arena);
+    assert_eq!(DROPS.with(Cell::get), expected + 1);
+}
+
+#[test]
+fn test_typed_arena_drop_order() {
+    // Enough objects to span several chunks.
+    const N: u32 = 1000;
+
+    let order = RefCell::new(Vec::new());
+    let mut arena: TypedArena<DropRecorder<'_>> = TypedArena::default();
+    for id in 0..N {
+        arena.alloc(DropRecorder { id, order: &order });
+    }
+    assert!(arena.chunks.borrow().len() > 1);
+    arena.clear();
+    assert_eq!(*order.borrow(), (0..N).rev().collect::<Vec<_>>());
+
+    order.borrow_mut().clear();
+    for id in 0..N {
+        arena.alloc(DropRecorder { id, order: &order });
+    }
+    drop(arena);
+    assert_eq!(*order.borrow(), (0..N).rev().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_drop_arena() {
+    let counter = Cell::new(0);
+    let order = RefCell::new(Vec::new());
+    {
+        let arena = DropArena::default();
+        for id in 0..10 {
+            arena.alloc(DropRecorder { id, order: &order });
+            arena.alloc(DropCounter { count: &counter });
+        }
+        let num = arena.alloc(3);
+        assert_eq!(*num, 3);
+        let string = arena.alloc("hello".to_string());
+        assert_eq!(string, "hello");
+        assert_eq!(counter.get(), 0);
+    }
+    assert_eq!(counter.get(), 10);
+    assert_eq!(*order.borrow(), (0..10).rev().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_drop_arena_trait_objects() {
+    trait Describe {
+        fn describe(&self) -> String;
+    }
+    struct Named<'a> {
+        name: String,
+        _counter: DropCounter<'a>,
+    }
+    impl Describe for Named<'_> {
+        fn describe(&self) -> String {
+            self.name.clone()
+        }
+    }
+    struct Tagged<'a> {
+        tags: Vec<String>,
+        _counter: DropCounter<'a>,
+    }
+    impl Describe for Tagged<'_> {
+        fn describe(&self) -> String {
+            self.tags.join(",")
+        }
+    }
+
+    let counter = Cell::new(0);
+    {
+        let arena = DropArena::default();
+        let objects: Vec<&dyn Describe> = vec![
+            arena.alloc(Named {
+                name: String::from("a"),
+                _counter: DropCounter { count: &counter },
+            }),
+            arena.alloc(Tagged {
+                tags: vec![String::from("b"), String::from("c")],
+                _counter: DropCounter { count: &counter },
+            }),
+        ];
+        let descriptions: Vec<String> = objects.iter().map(|object| object.describe()).collect();
+        assert_eq!(descriptions, ["a", "b,c"]);
+        #[cfg(feature = "nightly")]
+        {
+            let object: &mut dyn Describe = arena.alloc_as(Named {
+                name: String::from("d"),
+                _counter: DropCounter { count: &counter },
+            });
+            assert_eq!(object.describe(), "d");
+        }
+        assert_eq!(counter.get(), 0);
+    }
+    assert_eq!(counter.get(), if cfg!(feature = "nightly") { 3 } else { 2 });
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_drop_arena_alloc_from_iter() {
+    let order = RefCell::new(Vec::new());
+    {
+        let arena = DropArena::default();
+        arena.alloc(DropRecorder {
+            id: 0,
+            order: &order,
+        });
+        let slice = arena.alloc_from_iter((1..4).map(|id| DropRecorder { id, order: &order }));
+        assert_eq!(slice.len(), 3);
+        arena.alloc(DropRecorder {
+            id: 4,
+            order: &order,
+        });
+    }
+    assert_eq!(*order.borrow(), [4, 3, 2, 1, 0]);
+}
+
+#[test]
+fn test_drop_arena_clear() {
+    let order = RefCell::new(Vec::new());
+    let mut arena = DropArena::default();
+    arena.alloc(DropRecorder {
+        id: 0,
+        order: &order,
+    });
+    arena.alloc(DropRecorder {
+        id: 1,
+        order: &order,
+    });
+    arena.clear();
+    assert_eq!(*order.borrow(), [1, 0]);
+    arena.alloc(DropRecorder {
+        id: 2,
+        order: &order,
+    });
+    drop(arena);
+    assert_eq!(*order.borrow(), [1, 0, 2]);
+}
+
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_declare_arena_drop_arena() {
+    thread_local! {
+        static DROPS: Cell<u32> = const { Cell::new(0) }
+    }
+    struct Unlisted;
+    impl Drop for Unlisted {
+        fn drop(&mut self) {
+            DROPS.with(|c| c.set(c.get() + 1));
+        }
+    }
+
+    crate::declare_arena!([
+        boxes: Box<i32>,
+    ], drops);
+
+    {
+        let arena = Arena::default();
+        let boxed = arena.alloc(Box::new(2));
+        assert_eq!(**boxed, 2);
+        assert_eq!(arena.alloc_from_iter([Box::new(3)]), &[Box::new(3)]);
+        assert_eq!(arena.alloc_slice(&[1, 2]), &[1, 2]);
+        assert_eq!(arena.alloc_str("hi"), "hi");
+        arena.alloc_any(Unlisted);
+        assert_eq!(arena.alloc_any_from_iter((0..3).map(|_| Unlisted)).len(), 3);
+        assert_eq!(*arena.alloc_any(7u64), 7);
+        assert_eq!(DROPS.with(|c| c.get()), 0);
+    }
+    assert_eq!(DROPS.with(|c| c.get()), 4);
+}
+
+#[test]
+fn test_arena_pool() {
+    #[cfg(not(miri))]
+    const ROUNDS: usize = 100;
+    #[cfg(miri)]
+    const ROUNDS: usize = 5;
+    const THREADS: usize = 4;
+
+    struct AtomicDropCounter<'a>(&'a AtomicUsize);
+
+    impl Drop for AtomicDropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let drops = AtomicUsize::new(0);
+    let pool = ArenaPool::default();
+    let dropless_pool = DroplessArenaPool::default();
+    std::thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for round in 0..ROUNDS {
+                    let arena = pool.checkout();
+                    // A reused arena has been cleared.
+                    assert!(arena.is_empty());
+                    for _ in 0..10 {
+                        arena.alloc(AtomicDropCounter(&drops));
+                    }
+                    let dropless = dropless_pool.checkout();
+                    assert_eq!(dropless.memory_used(), 0);
+                    assert_eq!(*dropless.alloc(round), round);
+                }
+            });
+        }
+    });
+    assert_eq!(drops.load(Ordering::Relaxed), THREADS * ROUNDS * 10);
+    // No more arenas were created than were in use at the same time.
+    assert!((1..=THREADS).contains(&pool.arenas.with(|arenas| arenas.len())));
+    assert!((1..=THREADS).contains(&dropless_pool.arenas.with(|arenas| arenas.len())));
+}
+
+#[test]
+fn test_pool_lock_released_on_panic() {
+    let lock: PoolLock<Vec<u32>> = PoolLock::default();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        lock.with(|values| {
+            values.push(1);
+            panic!("panic while locked");
+        })
+    }));
+    assert!(result.is_err());
+    // The pool stays usable.
+    assert_eq!(lock.with(|values| values.len()), 1);
+}
+
+#[test]
+fn test_sync_dropless_arena() {
+    #[cfg(not(miri))]
+    const N: u64 = 10_000;
+    #[cfg(miri)]
+    const N: u64 = 100;
+    const THREADS: u64 = 8;
+
+    let arena = SyncDroplessArena::default();
+    let refs: Vec<Vec<&u64>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let arena = &arena;
+                scope.spawn(move || (0..N).map(|i| &*arena.alloc(t * N + i)).collect::<Vec<_>>())
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut addresses = HashSet::new();
+    for (t, refs) in refs.iter().enumerate() {
+        for (i, r) in refs.iter().enumerate() {
+            assert_eq!(**r, t as u64 * N + i as u64);
+            assert!(addresses.insert(*r as *const u64));
+        }
+    }
+    assert_eq!(addresses.len() as u64, THREADS * N);
+
+    assert_eq!(arena.alloc_slice(&[1, 2, 3]), &[1, 2, 3]);
+    assert_eq!(arena.alloc_str("hello"), "hello");
+}
+
+#[test]
+fn test_str_interner() {
+    let interner = StrInterner::default();
+    let foo = interner.intern("foo");
+    let bar = interner.intern("bar");
+    let owned = "foo".to_string();
+    let foo_again = interner.intern(owned.as_str());
+    assert_eq!(foo, "foo");
+    assert_eq!(bar, "bar");
+    assert!(ptr::eq(foo, foo_again));
+    assert!(!ptr::eq(foo, bar));
+    assert_eq!(interner.intern(""), "");
+}
+
+#[test]
+fn test_slice_interner() {
+    let interner = SliceInterner::default();
+    let first = interner.intern_slice(&[1, 2, 3]);
+    let owned: Vec<i32> = (1..=3).collect();
+    let second = interner.intern_slice(&owned);
+    let other = interner.intern_slice(&[1, 2, 4]);
+    assert_eq!(first, &[1, 2, 3]);
+    assert!(ptr::eq(first, second));
+    assert!(!ptr::eq(first, other));
+    assert!(interner.intern_slice(&[]).is_empty());
+
+    // The elements don't need to be `'static`.
+    let owned = "a".to_string();
+    let interner = SliceInterner::default();
+    let strs = interner.intern_slice(&[owned.as_str(), "b"]);
+    assert!(ptr::eq(strs, interner.intern_slice(&["a", "b"])));
+
+    // Padding bytes don't take part in the comparison.
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    struct Padded {
+        a: u8,
+        b: u64,
     }
+    let interner = SliceInterner::default();
+    let first = interner.intern_slice(&[Padded { a: 1, b: 2 }]);
+    let padded = [Padded { a: 3, b: 4 }, Padded { a: 1, b: 2 }];
+    let second = interner.intern_slice(&padded[1..]);
+    assert!(ptr::eq(first, second));
+    assert!(first[0] == Padded { a: 1, b: 2 });
 }
 
+#[cfg(feature = "nightly")]
 #[test]
-fn test_typed_arena_clear() {
-    let mut arena = TypedArena::default();
-    for _ in 0..10 {
-        arena.clear();
-        #[cfg(not(miri))]
-        const N: usize = 10000;
-        #[cfg(miri)]
-        const N: usize = 100;
-        for _ in 0..N {
-            arena.alloc(Point { x: 1, y: 2, z: 3 });
-        }
+fn test_dropless_allocator() {
+    let arena = DroplessArena::default();
+    let mut vec: Vec<u32, &DroplessArena> = Vec::new_in(&arena);
+    for i in 0..1000 {
+        vec.push(i);
     }
+    assert!(vec.iter().copied().eq(0..1000));
+    let empty: Vec<(), &DroplessArena> = Vec::with_capacity_in(10, &arena);
+    assert!(empty.is_empty());
 }
 
-// #[bench]
-// fn bench_typed_arena_clear(b: &mut Bencher) {
-//     let mut arena = TypedArena::default();
-//     b.iter(|| {
-//         arena.alloc(Point { x: 1, y: 2, z: 3 });
-//         arena.clear();
-//     })
-// }
-//
-// #[bench]
-// fn bench_typed_arena_clear_100(b: &mut Bencher) {
-//     let mut arena = TypedArena::default();
-//     b.iter(|| {
-//         for _ in 0..100 {
-//             arena.alloc(Point { x: 1, y: 2, z: 3 });
-//         }
-//         arena.clear();
-//     })
-// }
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_declare_named_arenas() {
+    crate::declare_arena!(First: FirstAllocatable [
+        boxes: Box<i32>,
+    ]);
+    crate::declare_arena!(Second<'a>: SecondAllocatable [
+        strs: Vec<&'a str>,
+    ]);
 
-// Drop tests
+    let hello = "hello".to_string();
+    let first = First::default();
+    let second = Second::default();
 
-struct DropCounter<'a> {
-    count: &'a Cell<u32>,
-}
+    let boxed = first.alloc(Box::new(1));
+    assert_eq!(**boxed, 1);
+    assert_eq!(first.alloc_from_iter([Box::new(2)]), &[Box::new(2)]);
+    assert_eq!(first.alloc_slice(&[1, 2]), &[1, 2]);
+    assert_eq!(first.alloc_str("first"), "first");
 
-impl Drop for DropCounter<'_> {
-    fn drop(&mut self) {
-        self.count.set(self.count.get() + 1);
-    }
+    let strs = second.alloc(vec![hello.as_str()]);
+    assert_eq!(strs, &["hello"]);
+    assert_eq!(second.alloc_from_iter([vec!["a"]]), &[vec!["a"]]);
+    assert_eq!(second.alloc_slice(&[3]), &[3]);
+    assert_eq!(second.alloc_str("second"), "second");
 }
 
+#[cfg(feature = "from-iter")]
 #[test]
-fn test_typed_arena_drop_count() {
+fn test_declare_arena_generic_fields() {
     let counter = Cell::new(0);
-    {
-        let arena: TypedArena<DropCounter<'_>> = TypedArena::default();
-        for _ in 0..100 {
-            // Allocate something with drop glue to make sure it doesn't leak.
-            arena.alloc(DropCounter { count: &counter });
-        }
-    };
-    assert_eq!(counter.get(), 100);
+    crate::declare_arena!(GenericArena<'tcx> [
+        slices: &'tcx [NotCopyNotDrop],
+        nested: Vec<Box<i32>>,
+        counters: Vec<Box<DropCounter<'tcx>>>,
+    ]);
+
+    let values = [NotCopyNotDrop { value: 1 }, NotCopyNotDrop { value: 2 }];
+    let mut arena = GenericArena::default();
+    // Listed `Copy` types have to pick one of the two impls that apply to them.
+    let slice: &mut &[NotCopyNotDrop] = arena.alloc::<_, crate::IsCopy>(&values[..]);
+    assert_eq!(arena.alloc::<_, crate::IsNotCopy>(&values[1..]).len(), 1);
+    assert_eq!(slice.len(), 2);
+    let nested = arena.alloc(vec![Box::new(1), Box::new(2)]);
+    assert_eq!(nested, &[Box::new(1), Box::new(2)]);
+    arena.alloc(vec![Box::new(DropCounter { count: &counter })]);
+    assert_eq!(arena.alloc_from_iter([vec![Box::new(3)]]).len(), 1);
+    // Types that need dropping are routed to their typed sub-arena, the rest to the dropless one.
+    assert_eq!(arena.nested.len(), 2);
+    assert_eq!(arena.counters.len(), 1);
+    assert!(arena.slices.is_empty());
+
+    arena.clear();
+    assert_eq!(counter.get(), 1);
+    assert!(arena.nested.is_empty());
 }
 
 #[test]
-fn test_typed_arena_drop_on_clear() {
-    let counter = Cell::new(0);
-    let mut arena: TypedArena<DropCounter<'_>> = TypedArena::default();
-    for i in 0..10 {
-        for _ in 0..100 {
-            // Allocate something with drop glue to make sure it doesn't leak.
-            arena.alloc(DropCounter { count: &counter });
-        }
-        arena.clear();
-        assert_eq!(counter.get(), i * 100 + 100);
+fn test_dropless_clear() {
+    let mut arena = DroplessArena::default();
+    for i in 0..10_000u32 {
+        arena.alloc(i);
     }
+    assert!(arena.chunks.borrow().len() > 1);
+    let reserved = arena.chunks.borrow().last().unwrap().storage.len();
+    arena.clear();
+    assert_eq!(arena.chunks.borrow().len(), 1);
+    assert_eq!(arena.stats().bytes_used, 0);
+    assert_eq!(arena.stats().bytes_reserved, reserved);
+    assert_eq!(*arena.alloc(5u32), 5);
+    assert!(arena.stats().bytes_used >= size_of::<u32>());
 }
 
-thread_local! {
-    static DROP_COUNTER: Cell<u32> = Cell::new(0)
+#[test]
+fn test_dropless_iter_chunks() {
+    let arena = DroplessArena::default();
+    assert_eq!(arena.iter_chunks().count(), 0);
+    for i in 0..10_000u32 {
+        arena.alloc(i);
+    }
+    arena.alloc_str("hello");
+    let mut ranges: Vec<(usize, usize)> = arena
+        .iter_chunks()
+        .map(|(ptr, len)| (ptr.addr(), ptr.addr() + len))
+        .collect();
+    assert_eq!(ranges.len(), arena.chunks.borrow().len());
+    assert!(ranges.len() > 1);
+    let total: usize = ranges.iter().map(|(start, end)| end - start).sum();
+    assert_eq!(total, arena.memory_used());
+    for &(start, end) in &ranges {
+        assert!(arena.contains_ptr(start as *const u8));
+        assert!(arena.contains_ptr((end - 1) as *const u8));
+    }
+    ranges.sort();
+    assert!(ranges.windows(2).all(|pair| pair[0].1 <= pair[1].0));
 }
 
-#[allow(unused)]
-struct SmallDroppable(u8);
+#[test]
+fn test_dropless_absorb() {
+    let mut arena = DroplessArena::default();
+    let ours = ptr::from_ref(arena.alloc_str("ours"));
+    let other = DroplessArena::default();
+    let theirs = ptr::from_ref(other.alloc_slice(&[1u64, 2, 3]));
+    for i in 0..10_000u32 {
+        other.alloc(i);
+    }
+    let other_used = other.memory_used();
+    let other_chunks = other.chunks.borrow().len();
+    let used = arena.memory_used();
+    arena.absorb(other);
+    assert_eq!(arena.chunks.borrow().len(), other_chunks + 1);
+    assert_eq!(arena.memory_used(), used + other_used);
+    // SAFETY: `arena` owns the chunks now and hasn't been cleared.
+    assert_eq!(unsafe { (&*ours, &*theirs) }, ("ours", &[1, 2, 3][..]));
 
-impl Drop for SmallDroppable {
-    fn drop(&mut self) {
-        DROP_COUNTER.with(|c| c.set(c.get() + 1));
+    // Allocation continues in the chunk that was current before.
+    let start = arena.start.get();
+    assert_eq!(*arena.alloc(4u64), 4);
+    assert_eq!(arena.start.get(), start);
+    assert_eq!(arena.memory_used(), used + other_used + size_of::<u64>());
+
+    // An empty arena takes over the chunks as they are.
+    let mut empty = DroplessArena::default();
+    empty.absorb(arena);
+    assert_eq!(empty.chunks.borrow().len(), other_chunks + 1);
+    assert_eq!(unsafe { (&*ours, &*theirs) }, ("ours", &[1, 2, 3][..]));
+    assert_eq!(*empty.alloc(7u32), 7);
+    empty.absorb(DroplessArena::default());
+    assert_eq!(empty.chunks.borrow().len(), other_chunks + 1);
+}
+
+#[test]
+fn test_dropless_absorb_then_clear() {
+    let mut arena = DroplessArena::default();
+    arena.alloc(1u64);
+    let other = DroplessArena::default();
+    other.alloc_slice(&[0u8; 100 * 1024]);
+    for i in 0..9u32 {
+        other.alloc(i);
     }
+    let longest = other
+        .chunks
+        .borrow()
+        .iter()
+        .map(|chunk| chunk.storage.len())
+        .max()
+        .unwrap();
+    assert!(longest > arena.memory_reserved());
+    arena.absorb(other);
+    assert_eq!(arena.total_allocations(), 11);
+    assert_eq!(arena.allocations_since_clear(), 11);
+
+    // The foreign chunk is longer than the last chunk, so `clear` keeps it instead.
+    arena.clear();
+    assert_eq!(arena.memory_reserved(), longest);
+    assert_eq!(arena.allocations_since_clear(), 0);
+    assert_eq!(*arena.alloc(2u64), 2);
 }
 
 #[test]
-fn test_typed_arena_drop_small_count() {
-    DROP_COUNTER.with(|c| c.set(0));
-    {
-        let arena: TypedArena<SmallDroppable> = TypedArena::default();
-        for _ in 0..100 {
-            // Allocate something with drop glue to make sure it doesn't leak.
-            arena.alloc(SmallDroppable(0));
+fn test_dropless_clear_with() {
+    for policy in [
+        ClearPolicy::KeepLongest,
+        ClearPolicy::KeepFirst,
+        ClearPolicy::KeepNone,
+    ] {
+        let mut arena = DroplessArena::default();
+        for i in 0..10_000u32 {
+            arena.alloc(i);
         }
-        // dropping
-    };
-    assert_eq!(DROP_COUNTER.with(|c| c.get()), 100);
+        let chunks = arena.chunks.borrow().len();
+        assert!(chunks > 1);
+        let first = arena.chunks.borrow()[0].storage.len();
+        let longest = arena.chunks.borrow()[chunks - 1].storage.len();
+        arena.clear_with(policy);
+        assert_eq!(arena.memory_used(), 0);
+        let expected = match policy {
+            ClearPolicy::KeepLongest => longest,
+            ClearPolicy::KeepFirst => first,
+            ClearPolicy::KeepNone => 0,
+        };
+        assert_eq!(arena.memory_reserved(), expected);
+        assert_eq!(*arena.alloc(5u32), 5);
+        assert_eq!(
+            arena.memory_reserved() == expected,
+            policy != ClearPolicy::KeepNone
+        );
+    }
 }
 
-// #[bench]
-// fn bench_noncopy(b: &mut Bencher) {
-//     let arena = TypedArena::default();
-//     b.iter(|| {
-//         arena.alloc(Noncopy { string: "hello world".to_string(), array: vec![1, 2, 3, 4, 5] })
-//     })
-// }
-//
-// #[bench]
-// fn bench_noncopy_nonarena(b: &mut Bencher) {
-//     b.iter(|| {
-//         let _: Box<_> =
-//             Box::new(Noncopy { string: "hello world".to_string(), array: vec![1, 2, 3, 4, 5] });
-//     })
-// }
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_declare_arena_clear() {
+    let counter = Cell::new(0);
+    crate::declare_arena!(CountingArena<'a> [
+        counters: DropCounter<'a>,
+        boxes: Box<i32>,
+    ]);
 
-struct WithInternalRef<'a> {
-    number: u32,
-    next: Option<&'a WithInternalRef<'a>>,
+    let mut arena = CountingArena::default();
+    for round in 1..=3 {
+        for i in 0..100 {
+            arena.alloc(DropCounter { count: &counter });
+            arena.alloc(Box::new(i));
+            arena.alloc(i);
+        }
+        arena.clear();
+        assert_eq!(counter.get(), round * 100);
+    }
 }
 
+#[cfg(feature = "from-iter")]
 #[test]
-fn test_dropless_with_reference() {
-    let arena = DroplessArena::default();
-    let second = arena.alloc(WithInternalRef {
-        number: 2,
-        next: None,
-    });
-    let first = arena.alloc(WithInternalRef {
-        number: 1,
-        next: Some(second),
-    });
-    assert_eq!(first.number, 1);
-    assert_eq!(first.next.unwrap().number, 2);
-}
+fn test_alloc_from_iter_reserves_lower_bound() {
+    /// Reports only a lower bound for the length of the wrapped iterator.
+    struct LowerBound<I>(I);
+    impl<I: Iterator> Iterator for LowerBound<I> {
+        type Item = I::Item;
+        fn next(&mut self) -> Option<I::Item> {
+            self.0.next()
+        }
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.0.size_hint().0, None)
+        }
+    }
+
+    let arena: TypedArena<Point> = TypedArena::default();
+    arena.reserve(1000);
+    let points = LowerBound((0..1000).map(|x| Point { x, y: 0, z: 0 }));
+    // Only the buffer that collects the elements is allocated, and it isn't grown.
+    let (slice, allocations) = count_allocations(|| arena.alloc_from_iter(points));
+    assert_eq!((slice.len(), allocations), (1000, 1));
 
-#[test]
-fn test_dropless_slice() {
     let arena = DroplessArena::default();
-    let slice = arena.alloc_slice(&[1, 2, 3, 4, 5]);
-    assert_eq!(slice, &[1, 2, 3, 4, 5]);
+    arena.try_reserve(8192).unwrap();
+    let (slice, allocations) = count_allocations(|| arena.alloc_from_iter(LowerBound(0..1000u32)));
+    assert!(slice.iter().copied().eq(0..1000));
+    assert_eq!(allocations, 1);
 }
 
+#[cfg(feature = "from-iter")]
 #[test]
-fn test_dropless_str() {
-    let arena = DroplessArena::default();
-    let string = arena.alloc_str("hello world");
-    assert_eq!(string, "hello world");
+fn test_declare_arena_empty_results() {
+    crate::declare_arena!([
+        lists: Vec<u32>,
+    ]);
+
+    /// Holds results of the arena for the arena's lifetime.
+    struct Item<'a> {
+        slice: &'a [u32],
+        name: &'a str,
+        lists: &'a [Vec<u32>],
+    }
+
+    // The empty results are `'static`, which coerces to the arena's lifetime.
+    fn build<'a>(arena: &'a Arena, values: &[u32], name: &str) -> Item<'a> {
+        Item {
+            slice: arena.alloc_slice(values),
+            name: arena.alloc_str(name),
+            lists: arena.alloc_from_iter(Some(values.to_vec()).filter(|v| !v.is_empty())),
+        }
+    }
+
+    let arena = Arena::default();
+    let empty = build(&arena, &[], "");
+    let full = build(&arena, &[1, 2], "full");
+    assert!(empty.slice.is_empty() && empty.name.is_empty() && empty.lists.is_empty());
+    assert_eq!((full.slice, full.name), (&[1, 2][..], "full"));
+    assert_eq!(full.lists, &[vec![1, 2]]);
 }
 
 #[cfg(feature = "from-iter")]
-#[derive(Debug, PartialEq, Eq)]
-struct NotCopyNotDrop {
-    value: i32,
+#[test]
+fn test_declare_arena_alloc_slice_clone_dispatch() {
+    crate::declare_arena!([
+        cells: Cell<u32>,
+        strings: String,
+    ]);
+
+    let arena = Arena::default();
+    // Declared types that need dropping are cloned into their `TypedArena`.
+    let strings = [String::from("a"), String::from("b")];
+    assert_eq!(arena.alloc_slice_clone(&strings), &strings);
+    assert_eq!(arena.strings.len(), 2);
+    assert_eq!(arena.dropless.memory_used(), 0);
+    // Declared types that don't are cloned into the `DroplessArena`.
+    let cells = arena.alloc_slice_clone(&[Cell::new(1), Cell::new(2)]);
+    cells[0].set(3);
+    assert_eq!(cells, &[Cell::new(3), Cell::new(2)]);
+    assert!(arena.cells.is_empty());
+    // `Copy` types are copied into the `DroplessArena`.
+    let used = arena.dropless.memory_used();
+    let points = [Point { x: 1, y: 2, z: 3 }; 4];
+    assert_eq!(arena.alloc_slice_clone(&points), &points);
+    assert_eq!(arena.dropless.memory_used(), used + size_of_val(&points));
+    assert!(arena.alloc_slice_clone::<Point, _>(&[]).is_empty());
 }
 
 #[cfg(feature = "from-iter")]
 #[test]
-fn test_declare_arena() {
+fn test_declare_arena_alloc_slice_clone() {
     crate::declare_arena!([
-        ints: NotCopyNotDrop,
         boxes: Box<i32>,
+        rcs: Rc<i32>,
     ]);
 
-    let arena = Arena::default();
-
-    // `Copy` types that you want to allocate don't need to be mentioned in `declare_arena!`.
-    let num = arena.alloc(1);
-    assert_eq!(num, &1);
+    let mut arena = Arena::default();
+    let boxes = arena.alloc_slice_clone(&[Box::new(1), Box::new(2)]);
+    assert_eq!(boxes, &[Box::new(1), Box::new(2)]);
+    assert_eq!(arena.boxes.len(), 2);
+    assert!(arena.alloc_slice_clone::<Box<i32>, _>(&[]).is_empty());
+    assert_eq!(arena.alloc_slice_clone(&[3, 4]), &[3, 4]);
+    assert!(arena.boxes.len() == 2 && arena.rcs.is_empty());
 
-    let slice = arena.alloc_slice(&[10, 15, 17]);
-    assert_eq!(slice, &[10, 15, 17]);
+    let rc = Rc::new(5);
+    assert_eq!(arena.alloc_slice_clone(&[rc.clone(), rc.clone()]).len(), 2);
+    assert_eq!(Rc::strong_count(&rc), 3);
+    arena.clear();
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert!(arena.boxes.is_empty());
+}
 
-    let val = arena.alloc(NotCopyNotDrop { value: 2 });
-    assert_eq!(val.value, 2);
+#[cfg(feature = "from-iter")]
+#[test]
+fn test_declare_arena_alloc_from_iter_panic() {
+    let counter = Cell::new(0);
+    crate::declare_arena!(CountingArena<'a> [
+        counters: DropCounter<'a>,
+    ]);
 
-    let slice = arena.alloc_from_iter((1..3).into_iter().map(|n| NotCopyNotDrop { value: n }));
+    let mut arena = CountingArena::default();
     assert_eq!(
-        slice,
-        &[NotCopyNotDrop { value: 1 }, NotCopyNotDrop { value: 2 }]
+        arena
+            .alloc_from_iter((0..3).map(|_| DropCounter { count: &counter }))
+            .len(),
+        3
     );
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_from_iter((0..10).map(|i| {
+            if i == 6 {
+                panic!("boom");
+            }
+            DropCounter { count: &counter }
+        }));
+    }));
+    assert!(result.is_err());
+    // Only the six collected elements were dropped.
+    assert_eq!(counter.get(), 6);
+    assert_eq!(arena.counters.iter().count(), 3);
 
-    let boxed = arena.alloc(Box::new(2));
-    assert_eq!(boxed, &Box::new(2));
-
-    let string = arena.alloc_str("hello world");
-    assert_eq!(string, "hello world");
+    assert_eq!(
+        arena
+            .alloc_from_iter((0..2).map(|_| DropCounter { count: &counter }))
+            .len(),
+        2
+    );
+    arena.clear();
+    assert_eq!(counter.get(), 11);
+    arena.alloc_from_iter((0..4).map(|_| DropCounter { count: &counter }));
+    drop(arena);
+    assert_eq!(counter.get(), 15);
 }
 
 struct CycleParticipant<'a> {
@@ -339,3 +3007,202 @@ fn test_cycle() {
     a.other.set(Some(b));
     b.other.set(Some(a));
 }
+
+#[test]
+fn test_thread_local_arena() {
+    crate::thread_local_arena!(static SCRATCH: DroplessArena, fn with_scratch;);
+    crate::thread_local_arena!(
+        static UNNAMED: DroplessArena;
+    );
+    crate::thread_local_arena!(
+        /// A typed arena per thread.
+        fn with_strings: TypedArena<String>;
+    );
+
+    let used = with_scratch(|arena| {
+        arena.alloc_slice(&[1u64, 2, 3]);
+        arena.memory_used()
+    });
+    assert_eq!(used, 24);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            // The other thread's arena is separate.
+            assert_eq!(with_scratch(|arena| arena.memory_used()), 0);
+            with_scratch(|arena| {
+                arena.alloc(1u64);
+            });
+            assert_eq!(with_scratch(|arena| arena.memory_used()), 8);
+            with_strings(|arena| {
+                arena.alloc(String::from("other"));
+            });
+        });
+    });
+    // Allocations persist across calls on the same thread.
+    assert_eq!(with_scratch(|arena| arena.memory_used()), 24);
+    assert_eq!(SCRATCH.with(|arena| arena.memory_used()), 24);
+    assert_eq!(UNNAMED.with(|arena| arena.memory_used()), 0);
+    assert_eq!(with_strings(|arena| arena.len()), 0);
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn test_dropless_alloc_as() {
+    use core::fmt::Debug;
+
+    trait Area {
+        fn area(&self) -> i32;
+    }
+    impl Area for Point {
+        fn area(&self) -> i32 {
+            self.x * self.y
+        }
+    }
+
+    let arena = DroplessArena::default();
+    let shapes: [&dyn Area; 2] = [
+        arena.alloc_as(Point { x: 2, y: 3, z: 0 }),
+        arena.alloc_as(Point { x: 4, y: 5, z: 0 }),
+    ];
+    assert_eq!(shapes.iter().map(|shape| shape.area()).sum::<i32>(), 26);
+    let slice: &mut [u16] = arena.alloc_as([1, 2, 3]);
+    slice[0] = 7;
+    assert_eq!(slice, &[7, 2, 3]);
+    let debug: &dyn Debug = arena.alloc_as("str");
+    assert_eq!(format!("{debug:?}"), "\"str\"");
+}
+
+#[test]
+fn test_alloc_shared() {
+    let arena = DroplessArena::default();
+    let a = arena.alloc_shared(CycleParticipant {
+        other: Cell::new(None),
+    });
+    let b = arena.alloc_shared(CycleParticipant {
+        other: Cell::new(None),
+    });
+    // Shared references can be stored in several places at once.
+    let (first, second) = (a, a);
+    first.other.set(Some(b));
+    b.other.set(Some(second));
+    assert!(ptr::eq(a.other.get().unwrap(), b));
+    assert!(ptr::eq(b.other.get().unwrap(), a));
+
+    let counter = Cell::new(0);
+    let typed = TypedArena::default();
+    let x = typed.alloc_shared(DropCounter { count: &counter });
+    let refs = [x, x, x];
+    assert!(refs.iter().all(|r| ptr::eq(*r, x)));
+    drop(typed);
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn test_dropless_alloc_str_list() {
+    let arena = DroplessArena::default();
+    let list = arena.alloc_str_list(&["alpha", "beta"]);
+    assert_eq!(list, ["alpha", "beta"]);
+    // The strings are adjacent, and the list itself is in the arena, too.
+    assert_eq!(list[0].as_ptr().wrapping_add(5), list[1].as_ptr());
+    assert!(arena.contains_ptr(list[0].as_ptr()));
+    assert!(arena.contains_ptr(list[1].as_ptr()));
+    assert!(arena.contains_ptr(list.as_ptr()));
+
+    assert_eq!(arena.alloc_str_list(&["", "x", ""]), ["", "x", ""]);
+    assert_eq!(arena.alloc_str_list(&["", ""]), ["", ""]);
+    assert!(arena.alloc_str_list(&[]).is_empty());
+}
+
+#[test]
+fn test_dropless_alloc_pinned() {
+    #[derive(Clone, Copy)]
+    struct Pinned {
+        value: u32,
+        _pin: PhantomPinned,
+    }
+
+    fn read(pinned: Pin<&mut Pinned>) -> (u32, *const Pinned) {
+        let pinned = pinned.into_ref().get_ref();
+        (pinned.value, pinned)
+    }
+
+    fn increment(mut value: Pin<&mut u32>) {
+        *value += 1;
+    }
+
+    let arena = DroplessArena::default();
+    let mut pinned = arena.alloc_pinned(Pinned {
+        value: 7,
+        _pin: PhantomPinned,
+    });
+    let addr: *const Pinned = &*pinned;
+    assert_eq!(read(pinned.as_mut()), (7, addr));
+    // Other allocations don't move it.
+    for x in 0..1000u64 {
+        arena.alloc(x);
+    }
+    assert_eq!(read(pinned), (7, addr));
+
+    let mut counter = arena.alloc_pinned(1u32);
+    increment(counter.as_mut());
+    assert_eq!(*counter, 2);
+}
+
+#[cfg(feature = "bumpalo-compat")]
+#[test]
+fn test_bump_compat() {
+    use crate::compat::Bump;
+
+    struct Doggo {
+        cuteness: u64,
+        age: u8,
+        scritches_required: bool,
+    }
+
+    // The example from the documentation of `bumpalo`.
+    let bump = Bump::new();
+    let scooter = bump.alloc(Doggo {
+        cuteness: u64::MAX,
+        age: 8,
+        scritches_required: true,
+    });
+    assert!(scooter.scritches_required);
+    scooter.age += 1;
+    assert_eq!((scooter.cuteness, scooter.age), (u64::MAX, 9));
+
+    assert_eq!(bump.alloc_slice_copy(&[1, 2, 3]), &[1, 2, 3]);
+    assert_eq!(bump.alloc_slice_copy::<u8>(&[]), &[]);
+    assert_eq!(bump.alloc_slice_copy(&[(), ()]).len(), 2);
+    let hello = bump.alloc_str("hello world");
+    hello.make_ascii_uppercase();
+    assert_eq!(hello, "HELLO WORLD");
+    assert_eq!(bump.alloc_str(""), "");
+
+    // Unlike `DroplessArena`, `Bump` accepts values that need to be dropped, and leaks them.
+    let counter = Cell::new(0);
+    bump.alloc(DropCounter { count: &counter });
+    bump.alloc(());
+    drop(bump);
+    assert_eq!(counter.get(), 0);
+}
+
+#[cfg(feature = "bumpalo-compat")]
+#[test]
+fn test_bump_compat_reset() {
+    use crate::compat::Bump;
+
+    // The example from the documentation of `bumpalo::Bump::reset`.
+    let mut bump = Bump::new();
+    for i in 0..10_000u32 {
+        bump.alloc(i);
+    }
+    let allocated = bump.allocated_bytes();
+    bump.reset();
+    assert!(bump.allocated_bytes() < allocated);
+    let kept = bump.allocated_bytes();
+    for j in 200..400u32 {
+        bump.alloc(j);
+    }
+    assert_eq!(bump.allocated_bytes(), kept);
+
+    assert!(Bump::with_capacity(100_000).allocated_bytes() >= 100_000);
+}
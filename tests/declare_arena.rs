@@ -0,0 +1,68 @@
+#![cfg(feature = "from-iter")]
+
+mod private {
+    stable_arena::declare_arena!(pub(crate) [
+        boxes: Box<i32>,
+    ]);
+}
+
+mod hidden {
+    stable_arena::declare_arena!(pub(self) []);
+
+    pub fn sum() -> i32 {
+        let arena = Arena::default();
+        *arena.alloc(1) + *arena.alloc(2)
+    }
+}
+
+#[test]
+fn test_declare_arena_visibility() {
+    let arena = private::Arena::default();
+    let boxed = arena.alloc(Box::new(5));
+    assert_eq!(**boxed, 5);
+    assert_eq!(arena.alloc_from_iter([Box::new(1), Box::new(2)]).len(), 2);
+    assert_eq!(arena.alloc_slice(&[1, 2]), &[1, 2]);
+    assert_eq!(arena.alloc_str("hi"), "hi");
+    assert_eq!(hidden::sum(), 3);
+}
+
+mod nested {
+    pub mod inner {
+        // Only the macro is imported: everything it expands to must resolve through `$crate`.
+        use stable_arena::declare_arena;
+
+        declare_arena!([
+            strings: String,
+        ]);
+    }
+}
+
+#[test]
+fn test_declare_arena_outside_crate_root() {
+    let arena = nested::inner::Arena::default();
+    let n: &mut u32 = arena.alloc(3);
+    assert_eq!(*n, 3);
+    let s = arena.alloc(String::from("hello"));
+    assert_eq!(s, "hello");
+    assert_eq!(arena.alloc_from_iter([String::from("a")]), &["a"]);
+    assert_eq!(arena.alloc_slice(&[1u8]), &[1]);
+    assert_eq!(arena.alloc_str("hi"), "hi");
+}
+
+mod attributes {
+    stable_arena::declare_arena!(
+        /// An arena with attributes.
+        #[derive(Debug)]
+        #[must_use]
+        pub(crate) [
+            strings: String,
+        ]
+    );
+}
+
+#[test]
+fn test_declare_arena_attributes() {
+    let arena = attributes::Arena::default();
+    arena.alloc(String::from("hello"));
+    assert!(format!("{arena:?}").starts_with("Arena {"));
+}